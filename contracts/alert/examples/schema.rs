@@ -1,10 +1,20 @@
 use std::env::current_dir;
 use std::fs::create_dir_all;
+use std::path::PathBuf;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use Alert::msg::{HandleMsg, InitMsg, QueryMsg};
-use Alert::state::State;
+use asteroid_alert::models::{Alert, AlertTemplate, Subscription};
+use asteroid_alert::msg::{
+    GetAlertResponse, GetAlertStatsResponse, GetAlertsResponse, GetChannelsResponse,
+    GetCreatorsResponse, GetKeySchemeResponse, GetLimitsResponse, GetNotificationsResponse,
+    GetNotifierPubkeyResponse, GetReportersResponse, GetStatsResponse,
+    GetSubscriberOverviewResponse, GetSubscribersForAlertResponse, GetSubscriptionResponse,
+    GetSubscriptionsForAddressResponse, GetTemplateResponse, GetTreasuryResponse, HandleMsg,
+    InitMsg, IsSubscribedResponse, ListBlockchainsResponse, ListProtocolsResponse,
+    ListTemplatesResponse, MigrateMsg, QueryMsg,
+};
+use asteroid_alert::state::Config;
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -12,8 +22,39 @@ fn main() {
     create_dir_all(&out_dir).unwrap();
     remove_schemas(&out_dir).unwrap();
 
-    export_schema(&schema_for!(InitMsg), &out_dir);
-    export_schema(&schema_for!(HandleMsg), &out_dir);
-    export_schema(&schema_for!(QueryMsg), &out_dir);
-    export_schema(&schema_for!(State), &out_dir);
+    export_schemas(&out_dir);
+}
+
+// Also used by tests/schema.rs to check that the checked-in schemas are up to date
+pub fn export_schemas(out_dir: &PathBuf) {
+    export_schema(&schema_for!(InitMsg), out_dir);
+    export_schema(&schema_for!(HandleMsg), out_dir);
+    export_schema(&schema_for!(QueryMsg), out_dir);
+    export_schema(&schema_for!(MigrateMsg), out_dir);
+    export_schema(&schema_for!(Config), out_dir);
+    export_schema(&schema_for!(Alert), out_dir);
+    export_schema(&schema_for!(AlertTemplate), out_dir);
+    export_schema(&schema_for!(Subscription), out_dir);
+
+    export_schema(&schema_for!(GetAlertsResponse), out_dir);
+    export_schema(&schema_for!(ListBlockchainsResponse), out_dir);
+    export_schema(&schema_for!(ListProtocolsResponse), out_dir);
+    export_schema(&schema_for!(GetAlertResponse), out_dir);
+    export_schema(&schema_for!(GetTemplateResponse), out_dir);
+    export_schema(&schema_for!(ListTemplatesResponse), out_dir);
+    export_schema(&schema_for!(GetSubscriptionsForAddressResponse), out_dir);
+    export_schema(&schema_for!(GetSubscriptionResponse), out_dir);
+    export_schema(&schema_for!(IsSubscribedResponse), out_dir);
+    export_schema(&schema_for!(GetSubscribersForAlertResponse), out_dir);
+    export_schema(&schema_for!(GetCreatorsResponse), out_dir);
+    export_schema(&schema_for!(GetNotificationsResponse), out_dir);
+    export_schema(&schema_for!(GetSubscriberOverviewResponse), out_dir);
+    export_schema(&schema_for!(GetChannelsResponse), out_dir);
+    export_schema(&schema_for!(GetNotifierPubkeyResponse), out_dir);
+    export_schema(&schema_for!(GetReportersResponse), out_dir);
+    export_schema(&schema_for!(GetTreasuryResponse), out_dir);
+    export_schema(&schema_for!(GetLimitsResponse), out_dir);
+    export_schema(&schema_for!(GetKeySchemeResponse), out_dir);
+    export_schema(&schema_for!(GetStatsResponse), out_dir);
+    export_schema(&schema_for!(GetAlertStatsResponse), out_dir);
 }
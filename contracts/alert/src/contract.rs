@@ -1,36 +1,145 @@
-use crate::models::{Alert, AlertField, OrderBy, Subscription, SubscriptionFieldValue};
+use crate::error::ContractError;
+use crate::events::{
+    format_category, format_channel_type, format_coin, format_expires_at, format_price,
+    format_tags, format_template_id, hash_field_values, hash_fields, NONE,
+};
+use crate::keys::AlertKey;
+use crate::models::{
+    Alert, AlertField, AlertPrice, AlertSubscriber, AlertTemplate, AlertTemplateRef,
+    BlockchainInfo, Channel, ChannelType, KeyScheme, Notification, OrderBy, ProtocolInfo, Severity,
+    Subscription, SubscriptionFieldValue, SubscriptionLimits,
+};
 use crate::msg::{
-    GetAlertsResponse, GetSubscriptionsForAddressResponse, HandleMsg, InitMsg, QueryMsg,
+    GetAlertResponse, GetAlertStatsResponse, GetAlertsResponse, GetChannelsResponse,
+    GetCreatorsResponse, GetKeySchemeResponse, GetLimitsResponse, GetNotificationsResponse,
+    GetNotifierPubkeyResponse, GetReportersResponse, GetStatsResponse,
+    GetSubscriberOverviewResponse, GetSubscribersForAlertResponse, GetSubscriptionResponse,
+    GetSubscriptionsForAddressResponse, GetTemplateResponse, GetTreasuryResponse, HandleMsg,
+    InitMsg, IsSubscribedResponse, ListBlockchainsResponse, ListProtocolsResponse,
+    ListTemplatesResponse, MigrateMsg, QueryMsg,
 };
 use crate::state::{
-    read_alert, read_alerts, read_config, read_subscription_for_address,
-    read_subscriptions_for_address, remove_subscription_for_address, store_alert, store_config,
-    store_subscription_for_address, Config,
+    count_alerts_for_template, count_subscriptions_for_address, count_subscriptions_for_alert,
+    count_unread_notifications, find_alert_key, may_read_alert, may_read_channel,
+    may_read_notification, may_read_subscription_for_address, may_read_template, migrate_v1_to_v2,
+    next_notification_id, next_subscription_id, next_template_id, read_alerts, read_alerts_by,
+    read_alerts_by_category, read_alerts_by_severity, read_alerts_by_tag, read_alerts_by_template,
+    read_blockchain_counts, read_channels, read_config, read_contract_version, read_migration_step,
+    read_notifications, read_protocol_counts, read_subscribers_for_alert,
+    read_subscriptions_for_address, read_subscriptions_for_address_and_alert, read_templates,
+    read_totals, remove_alert, remove_channel, remove_subscription_for_address,
+    remove_subscriptions_for_alert, remove_template, store_alert, store_channel, store_config,
+    store_contract_version, store_notification, store_subscription_for_address, store_template,
+    Config, CONTRACT_VERSION,
+};
+use crate::validation::{
+    check_field_value_limits, normalize_field_values, normalize_tag, normalize_tags,
+    validate_alert_fields, validate_alert_price, validate_channel, validate_key_scheme,
+    validate_subscription_limits,
 };
 use cosmwasm_std::{
-    to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, InitResponse,
-    Querier, StdError, StdResult, Storage,
+    log, to_binary, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern,
+    HandleResponse, HumanAddr, InitResponse, MigrateResponse, Querier, StdResult, Storage, Uint128,
 };
 
+// Keeps notifications small, details belong off-chain
+const MAX_PAYLOAD_LEN: usize = 1024;
+// Channels per address, a few are enough to cover every channel type
+const MAX_CHANNELS: usize = 10;
+// Subscriptions removed by a single DeleteAlert, so deleting a popular alert stays within gas
+const MAX_DELETED_SUBSCRIPTIONS: usize = 50;
+// Records a single MigrateMsg migrates when it sets no limit
+const DEFAULT_MIGRATION_LIMIT: u32 = 500;
+
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    _msg: InitMsg,
-) -> StdResult<InitResponse> {
+    msg: InitMsg,
+) -> Result<InitResponse, ContractError> {
+    let treasury_fee_percent: u64 = msg.treasury_fee_percent.unwrap_or(0);
+    if treasury_fee_percent > 100 {
+        return Err(ContractError::InvalidTreasuryFee {
+            fee_percent: treasury_fee_percent,
+        });
+    }
+    let limits: SubscriptionLimits = msg.limits.unwrap_or_default();
+    validate_subscription_limits(&limits)?;
+    let key_scheme: KeyScheme = msg.key_scheme.unwrap_or_default();
+    validate_key_scheme(&key_scheme)?;
+    let treasury: Option<CanonicalAddr> = match msg.treasury {
+        Some(treasury) => Some(deps.api.canonical_address(&treasury)?),
+        None => None,
+    };
+
     let config = Config {
         owner: deps.api.canonical_address(&env.message.sender)?,
+        creators: vec![],
+        treasury,
+        treasury_fee_percent,
+        reporters: vec![],
+        notifier_pubkey: None,
+        pending_owner: None,
+        limits,
+        key_scheme,
     };
 
     store_config(&mut deps.storage, &config)?;
+    store_contract_version(&mut deps.storage, CONTRACT_VERSION)?;
 
     Ok(InitResponse::default())
 }
 
+/**
+ * Migrates the storage from the stored contract version to CONTRACT_VERSION.
+ *
+ * Only versions with a migration to CONTRACT_VERSION are accepted. A contract that is already up
+ * to date migrates nothing, so code can be upgraded without a storage change. Large stores are
+ * migrated over several MigrateMsg, each handling at most `limit` records. The version is only
+ * stored once the migration finished, handle messages and queries are rejected until then.
+ */
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    msg: MigrateMsg,
+) -> Result<MigrateResponse, ContractError> {
+    let from: u64 = read_contract_version(&deps.storage)?;
+    let limit: usize = msg.limit.unwrap_or(DEFAULT_MIGRATION_LIMIT).max(1) as usize;
+    let complete: bool = match from {
+        1 => migrate_v1_to_v2(&mut deps.storage, limit)?,
+        CONTRACT_VERSION => true,
+        _ => {
+            return Err(ContractError::UnsupportedVersion {
+                from,
+                to: CONTRACT_VERSION,
+            })
+        }
+    };
+    if complete {
+        store_contract_version(&mut deps.storage, CONTRACT_VERSION)?;
+    }
+
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "migrate"),
+            log("from_version", from),
+            log("to_version", CONTRACT_VERSION),
+            log("complete", complete),
+        ],
+        data: None,
+    })
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
-) -> StdResult<HandleResponse> {
+) -> Result<HandleResponse, ContractError> {
+    // The handlers rely on the current storage layout
+    if read_migration_step(&deps.storage)?.is_some() {
+        return Err(ContractError::MigrationInProgress {});
+    }
+
     match msg {
         HandleMsg::CreateAlert {
             blockchain,
@@ -39,250 +148,4311 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             name,
             description,
             fields,
+            price,
+            template_id,
+            severity,
+            category,
+            tags,
         } => try_create_alert(
             deps,
             env,
-            blockchain,
-            protocol,
-            method,
+            CreateAlertParams {
+                blockchain,
+                protocol,
+                method,
+                name,
+                description,
+                fields,
+                price,
+                template_id,
+                severity,
+                category,
+                tags,
+            },
+        ),
+        HandleMsg::UpdateAlert {
+            alert_key,
             name,
             description,
-            fields,
+            new_fields,
+            severity,
+            category,
+            tags,
+        } => try_update_alert(
+            deps,
+            env,
+            UpdateAlertParams {
+                alert_key,
+                name,
+                description,
+                new_fields,
+                severity,
+                category,
+                tags,
+            },
         ),
+        HandleMsg::DeprecateAlert { alert_key } => try_deprecate_alert(deps, env, alert_key),
+        HandleMsg::DeleteAlert { alert_key } => try_delete_alert(deps, env, alert_key),
+        HandleMsg::ApplyTemplate { alert_key } => try_apply_template(deps, env, alert_key),
+        HandleMsg::SetAlertPrice { alert_key, price } => {
+            try_set_alert_price(deps, env, alert_key, price)
+        }
+        HandleMsg::CreateTemplate {
+            name,
+            description,
+            fields,
+        } => try_create_template(deps, env, name, description, fields),
+        HandleMsg::UpdateTemplate {
+            template_id,
+            name,
+            description,
+            new_fields,
+        } => try_update_template(deps, env, template_id, name, description, new_fields),
+        HandleMsg::DeleteTemplate { template_id } => try_delete_template(deps, env, template_id),
         HandleMsg::SubscribeAlert {
             alert_key,
             field_values,
-        } => try_subscribe_alert(deps, env, alert_key, field_values),
-        HandleMsg::UnsubscribeAlert { alert_key } => try_unsubscribe_alert(deps, env, alert_key),
+            channels,
+        } => try_subscribe_alert(deps, env, alert_key, field_values, channels),
+        HandleMsg::UpdateSubscription {
+            subscription_id,
+            field_values,
+            channels,
+        } => try_update_subscription(deps, env, subscription_id, field_values, channels),
+        HandleMsg::RenewSubscription { subscription_id } => {
+            try_renew_subscription(deps, env, subscription_id)
+        }
+        HandleMsg::UnsubscribeAlert { subscription_id } => {
+            try_unsubscribe_alert(deps, env, subscription_id)
+        }
+        HandleMsg::ReportTrigger {
+            alert_key,
+            subscriber,
+            subscription_id,
+            payload,
+            block_ref,
+        } => try_report_trigger(
+            deps,
+            env,
+            alert_key,
+            subscriber,
+            subscription_id,
+            payload,
+            block_ref,
+        ),
+        HandleMsg::AddCreator { creator } => try_add_creator(deps, env, creator),
+        HandleMsg::RemoveCreator { creator } => try_remove_creator(deps, env, creator),
+        HandleMsg::TransferOwnership { new_owner } => try_transfer_ownership(deps, env, new_owner),
+        HandleMsg::AcceptOwnership {} => try_accept_ownership(deps, env),
+        HandleMsg::AckNotifications { ids } => try_ack_notifications(deps, env, ids),
+        HandleMsg::AddReporter { reporter } => try_add_reporter(deps, env, reporter),
+        HandleMsg::RemoveReporter { reporter } => try_remove_reporter(deps, env, reporter),
+        HandleMsg::UpdateTreasury {
+            treasury,
+            treasury_fee_percent,
+        } => try_update_treasury(deps, env, treasury, treasury_fee_percent),
+        HandleMsg::SetChannel {
+            name,
+            channel_type,
+            ciphertext,
+        } => try_set_channel(deps, env, name, channel_type, ciphertext),
+        HandleMsg::RemoveChannel { name } => try_remove_channel(deps, env, name),
+        HandleMsg::SetNotifierPubkey { pubkey } => try_set_notifier_pubkey(deps, env, pubkey),
+        HandleMsg::UpdateLimits { limits } => try_update_limits(deps, env, limits),
     }
 }
 
+// The fields of HandleMsg::CreateAlert
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateAlertParams {
+    pub blockchain: String,
+    pub protocol: String,
+    pub method: String,
+    pub name: String,
+    pub description: String,
+    pub fields: Vec<AlertField>,
+    pub price: Option<AlertPrice>,
+    pub template_id: Option<u64>,
+    pub severity: Severity,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+}
+
 pub fn try_create_alert<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    blockchain: String,
-    protocol: String,
-    method: String,
-    name: String,
-    description: String,
-    fields: Vec<AlertField>,
-) -> StdResult<HandleResponse> {
+    params: CreateAlertParams,
+) -> Result<HandleResponse, ContractError> {
+    let CreateAlertParams {
+        blockchain,
+        protocol,
+        method,
+        name,
+        description,
+        fields,
+        price,
+        template_id,
+        severity,
+        category,
+        tags,
+    } = params;
     let config: Config = read_config(&deps.storage)?;
 
-    // Only owner can create alert for now
-    // TODO: Have some form of whitelisted addresses
-    if deps.api.canonical_address(&env.message.sender)? != config.owner {
-        return Err(StdError::unauthorized());
+    // Only the owner and whitelisted creators can create alerts
+    let sender_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    if sender_addr != config.owner && !config.creators.contains(&sender_addr) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let template: Option<AlertTemplate> = match template_id {
+        Some(template_id) => Some(
+            may_read_template(&deps.storage, template_id)?
+                .ok_or(ContractError::TemplateNotFound { template_id })?,
+        ),
+        None => None,
+    };
+    let (fields, description): (Vec<AlertField>, String) = match &template {
+        Some(template) => (
+            inherit_fields(&template.fields, fields),
+            if description.is_empty() {
+                template.description.clone()
+            } else {
+                description
+            },
+        ),
+        None => (fields, description),
+    };
+
+    // Make sure every field can be validated, so bad definitions never reach storage
+    validate_alert_fields(&fields)?;
+    if let Some(price) = &price {
+        validate_alert_price(price)?;
+    }
+    let category: Option<String> = match category {
+        Some(category) => Some(normalize_tag(&category)?),
+        None => None,
+    };
+    let tags: Vec<String> = normalize_tags(&tags)?;
+
+    // Owner alerts are global, everyone else creates alerts in their own namespace unless the key
+    // scheme turns namespaces off
+    let namespace: Option<&str> =
+        if sender_addr == config.owner || !config.key_scheme.namespace_creators {
+            None
+        } else {
+            Some(env.message.sender.as_str())
+        };
+    let key: AlertKey = AlertKey::new(
+        &config.key_scheme,
+        namespace,
+        &blockchain,
+        &protocol,
+        &method,
+    )?;
+    let alert_key: String = key.to_string();
+    // Overwriting an existing alert would break its subscriptions. Alerts created before keys
+    // were canonicalized can differ from the new key in case only, and would be shadowed by it.
+    if let Some(alert_key) = find_alert_key(&deps.storage, &alert_key)? {
+        return Err(ContractError::AlertAlreadyExists { alert_key });
     }
 
-    // Concatenate `Blockchain.Protocol.method` to use as alert_key
-    let alert_key: String = format!("{}.{}.{}", blockchain, protocol, method);
     let alert: Alert = Alert {
         alert_key,
-        blockchain,
-        protocol,
-        method,
+        blockchain: key.blockchain,
+        protocol: key.protocol,
+        method: key.method,
         name,
         description,
         fields,
+        creator: env.message.sender,
+        deprecated: false,
+        price,
+        template: template.map(|template| AlertTemplateRef {
+            template_id: template.template_id,
+            version: template.version,
+        }),
+        severity,
+        category,
+        tags,
     };
     store_alert(&mut deps.storage, &alert)?;
 
-    Ok(HandleResponse::default())
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "create_alert"),
+            log("alert_key", &alert.alert_key),
+            log("creator", &alert.creator),
+            log("fields_hash", hash_fields(&alert.fields)?),
+            log("price", format_price(&alert.price)),
+            log("template_id", format_template_id(&alert.template)),
+            log("severity", alert.severity.as_str()),
+            log("category", format_category(&alert.category)),
+            log("tags", format_tags(&alert.tags)),
+        ],
+        data: None,
+    })
 }
 
-pub fn try_subscribe_alert<S: Storage, A: Api, Q: Querier>(
+// Template fields overridden by a field with the same key are replaced in place, the other
+// fields are appended
+fn inherit_fields(template_fields: &[AlertField], overrides: Vec<AlertField>) -> Vec<AlertField> {
+    let mut fields: Vec<AlertField> = template_fields.to_vec();
+    for field in overrides {
+        match fields.iter_mut().find(|f| f.field_key == field.field_key) {
+            Some(inherited) => *inherited = field,
+            None => fields.push(field),
+        }
+    }
+    fields
+}
+
+// The fields of HandleMsg::UpdateAlert, None leaves the current value
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UpdateAlertParams {
+    pub alert_key: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub new_fields: Option<Vec<AlertField>>,
+    pub severity: Option<Severity>,
+    pub category: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+pub fn try_update_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    params: UpdateAlertParams,
+) -> Result<HandleResponse, ContractError> {
+    let UpdateAlertParams {
+        alert_key,
+        name,
+        description,
+        new_fields,
+        severity,
+        category,
+        tags,
+    } = params;
+    let mut alert: Alert = read_managed_alert(deps, &env, alert_key)?;
+
+    if let Some(name) = name {
+        alert.name = name;
+    }
+    if let Some(description) = description {
+        alert.description = description;
+    }
+    if let Some(new_fields) = new_fields {
+        // Existing subscriptions have no values for new fields, so they have to be optional
+        if let Some(field) = new_fields.iter().find(|field| !field.optional) {
+            return Err(ContractError::InvalidField {
+                field_key: field.field_key.clone(),
+                reason: "fields added to an existing alert must be optional".to_string(),
+            });
+        }
+        alert.fields.extend(new_fields);
+        validate_alert_fields(&alert.fields)?;
+    }
+    if let Some(severity) = severity {
+        alert.severity = severity;
+    }
+    if let Some(category) = category {
+        alert.category = if category.is_empty() {
+            None
+        } else {
+            Some(normalize_tag(&category)?)
+        };
+    }
+    if let Some(tags) = tags {
+        alert.tags = normalize_tags(&tags)?;
+    }
+    store_alert(&mut deps.storage, &alert)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "update_alert"),
+            log("alert_key", &alert.alert_key),
+            log("sender", &env.message.sender),
+            log("fields_hash", hash_fields(&alert.fields)?),
+            log("severity", alert.severity.as_str()),
+            log("category", format_category(&alert.category)),
+            log("tags", format_tags(&alert.tags)),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_deprecate_alert<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     alert_key: String,
-    field_values: Vec<SubscriptionFieldValue>,
-) -> StdResult<HandleResponse> {
-    // This throws an error if the alert doesen't exist
-    // TODO: Better error message
-    let alert: Alert = read_alert(&deps.storage, &alert_key)?;
-    let alert_fields: Vec<AlertField> = alert.fields;
-    for alert_field in alert_fields {
-        let valid_field_value: Option<&SubscriptionFieldValue> = field_values
-            .iter()
-            .find(|field_value| field_value.field_key == alert_field.field_key);
-        // Throw error if a required value for a field is missing
-        if !valid_field_value.is_some() {
-            return Err(StdError::generic_err(format!(
-                "Missing field {}",
-                alert_field.field_key
-            )));
-        }
-        // Throw error if a field value does not satisfy regex expression
-        // if !Regex::new(&alert_field.validation_regex)
-        //     .unwrap()
-        //     .is_match(&valid_field_value.unwrap().value)
-        // {
-        //     return Err(StdError::generic_err(format!(
-        //         "Invalid field {}",
-        //         alert_field.field_key
-        //     )));
-        // }
+) -> Result<HandleResponse, ContractError> {
+    let mut alert: Alert = read_managed_alert(deps, &env, alert_key)?;
+
+    alert.deprecated = true;
+    store_alert(&mut deps.storage, &alert)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "deprecate_alert"),
+            log("alert_key", &alert.alert_key),
+            log("sender", &env.message.sender),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_delete_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alert_key: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut alert: Alert = read_managed_alert(deps, &env, alert_key)?;
+
+    let removed: Vec<(CanonicalAddr, Subscription)> = remove_subscriptions_for_alert(
+        &mut deps.storage,
+        &alert.alert_key,
+        MAX_DELETED_SUBSCRIPTIONS,
+    )?;
+    // The alert is only removed with its last subscription, until then it is deprecated so no
+    // subscriptions are added while the sender repeats DeleteAlert
+    let (remaining, _) = count_subscriptions_for_alert(&deps.storage, &alert.alert_key)?;
+    if remaining == 0 {
+        remove_alert(&mut deps.storage, &alert.alert_key)?;
+    } else if !alert.deprecated {
+        alert.deprecated = true;
+        store_alert(&mut deps.storage, &alert)?;
     }
 
-    let canonical_subscriber_addr: CanonicalAddr =
-        deps.api.canonical_address(&env.message.sender)?;
-    let subscription: Subscription = Subscription {
-        alert_key,
-        field_values,
+    // Subscribers get their deposits back since they can no longer unsubscribe themselves
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for (subscriber_addr, subscription) in removed {
+        if let Some(deposit) = subscription.deposit {
+            let subscriber: HumanAddr = deps.api.human_address(&subscriber_addr)?;
+            messages.push(send_coins(&env, subscriber, vec![deposit]));
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "delete_alert"),
+            log("alert_key", &alert.alert_key),
+            log("sender", &env.message.sender),
+            log("remaining_subscriptions", remaining),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_set_alert_price<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alert_key: String,
+    price: Option<AlertPrice>,
+) -> Result<HandleResponse, ContractError> {
+    let mut alert: Alert = read_managed_alert(deps, &env, alert_key)?;
+
+    if let Some(price) = &price {
+        validate_alert_price(price)?;
+    }
+    alert.price = price;
+    store_alert(&mut deps.storage, &alert)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_alert_price"),
+            log("alert_key", &alert.alert_key),
+            log("sender", &env.message.sender),
+            log("price", format_price(&alert.price)),
+        ],
+        data: None,
+    })
+}
+
+// Returns the key an alert is stored under, given its key in any case. Keys of alerts that don't
+// exist, for example deleted ones, are returned as given.
+fn resolve_alert_key<S: Storage>(storage: &S, alert_key: String) -> StdResult<String> {
+    Ok(find_alert_key(storage, &alert_key)?.unwrap_or(alert_key))
+}
+
+// Reads an alert, throwing an error if the sender is neither its creator, while still a creator,
+// nor the owner
+fn read_managed_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    alert_key: String,
+) -> Result<Alert, ContractError> {
+    let alert: Alert = may_read_alert(&deps.storage, &alert_key)?
+        .ok_or(ContractError::AlertNotFound { alert_key })?;
+
+    let config: Config = read_config(&deps.storage)?;
+    let sender_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    // Removed creators lose control of their alerts, the owner manages them from then on
+    let is_creator: bool = config.creators.contains(&sender_addr)
+        && !alert.creator.is_empty()
+        && deps.api.canonical_address(&alert.creator)? == sender_addr;
+    if sender_addr != config.owner && !is_creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(alert)
+}
+
+pub fn try_apply_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alert_key: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut alert: Alert = read_managed_alert(deps, &env, alert_key)?;
+    let template_id: u64 = match &alert.template {
+        Some(template) => template.template_id,
+        None => {
+            return Err(ContractError::NoTemplate {
+                alert_key: alert.alert_key,
+            })
+        }
     };
-    store_subscription_for_address(&mut deps.storage, canonical_subscriber_addr, subscription)?;
+    // Templates can't be deleted while alerts use them
+    let template: AlertTemplate = may_read_template(&deps.storage, template_id)?
+        .ok_or(ContractError::TemplateNotFound { template_id })?;
+
+    // Fields added to a template are optional, so they can be added to alerts with subscriptions
+    for field in template.fields {
+        if !alert.fields.iter().any(|f| f.field_key == field.field_key) {
+            alert.fields.push(field);
+        }
+    }
+    validate_alert_fields(&alert.fields)?;
+    alert.template = Some(AlertTemplateRef {
+        template_id,
+        version: template.version,
+    });
+    store_alert(&mut deps.storage, &alert)?;
 
-    Ok(HandleResponse::default())
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "apply_template"),
+            log("alert_key", &alert.alert_key),
+            log("sender", &env.message.sender),
+            log("template_id", template_id),
+            log("version", template.version),
+            log("fields_hash", hash_fields(&alert.fields)?),
+        ],
+        data: None,
+    })
 }
 
-pub fn try_unsubscribe_alert<S: Storage, A: Api, Q: Querier>(
+pub fn try_create_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    description: String,
+    fields: Vec<AlertField>,
+) -> Result<HandleResponse, ContractError> {
+    let config: Config = read_config(&deps.storage)?;
+
+    // Only the owner and whitelisted creators can create templates
+    let sender_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    if sender_addr != config.owner && !config.creators.contains(&sender_addr) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    validate_alert_fields(&fields)?;
+
+    let template: AlertTemplate = AlertTemplate {
+        template_id: next_template_id(&mut deps.storage)?,
+        name,
+        description,
+        fields,
+        creator: env.message.sender,
+        version: 1,
+    };
+    store_template(&mut deps.storage, &template)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "create_template"),
+            log("template_id", template.template_id),
+            log("creator", &template.creator),
+            log("fields_hash", hash_fields(&template.fields)?),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_update_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    template_id: u64,
+    name: Option<String>,
+    description: Option<String>,
+    new_fields: Option<Vec<AlertField>>,
+) -> Result<HandleResponse, ContractError> {
+    let mut template: AlertTemplate = read_managed_template(deps, &env, template_id)?;
+
+    if let Some(name) = name {
+        template.name = name;
+    }
+    if let Some(description) = description {
+        template.description = description;
+    }
+    if let Some(new_fields) = new_fields {
+        // Derived alerts may have subscriptions, so fields have to be optional like in UpdateAlert
+        if let Some(field) = new_fields.iter().find(|field| !field.optional) {
+            return Err(ContractError::InvalidField {
+                field_key: field.field_key.clone(),
+                reason: "fields added to an existing template must be optional".to_string(),
+            });
+        }
+        if !new_fields.is_empty() {
+            template.version += 1;
+        }
+        template.fields.extend(new_fields);
+        validate_alert_fields(&template.fields)?;
+    }
+    store_template(&mut deps.storage, &template)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "update_template"),
+            log("template_id", template_id),
+            log("sender", &env.message.sender),
+            log("version", template.version),
+            log("fields_hash", hash_fields(&template.fields)?),
+            log(
+                "alert_count",
+                count_alerts_for_template(&deps.storage, template_id)?,
+            ),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_delete_template<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    template_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    read_managed_template(deps, &env, template_id)?;
+
+    // Derived alerts would have nothing to apply updates from
+    let alert_count: u64 = count_alerts_for_template(&deps.storage, template_id)?;
+    if alert_count > 0 {
+        return Err(ContractError::TemplateInUse {
+            template_id,
+            alert_count,
+        });
+    }
+    remove_template(&mut deps.storage, template_id);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "delete_template"),
+            log("template_id", template_id),
+            log("sender", &env.message.sender),
+        ],
+        data: None,
+    })
+}
+
+// Reads a template, throwing an error if the sender is neither its creator, while still a creator,
+// nor the owner
+fn read_managed_template<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    template_id: u64,
+) -> Result<AlertTemplate, ContractError> {
+    let template: AlertTemplate = may_read_template(&deps.storage, template_id)?
+        .ok_or(ContractError::TemplateNotFound { template_id })?;
+
+    let config: Config = read_config(&deps.storage)?;
+    let sender_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    let is_creator: bool = config.creators.contains(&sender_addr)
+        && deps.api.canonical_address(&template.creator)? == sender_addr;
+    if sender_addr != config.owner && !is_creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(template)
+}
+
+pub fn try_subscribe_alert<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     alert_key: String,
-) -> StdResult<HandleResponse> {
+    field_values: Vec<SubscriptionFieldValue>,
+    channels: Vec<String>,
+) -> Result<HandleResponse, ContractError> {
+    let alert: Alert =
+        may_read_alert(&deps.storage, &alert_key)?.ok_or_else(|| ContractError::AlertNotFound {
+            alert_key: alert_key.clone(),
+        })?;
+    if alert.deprecated {
+        return Err(ContractError::AlertDeprecated {
+            alert_key: alert.alert_key,
+        });
+    }
+    // The limits bound the work of normalizing, normalized values are checked again since they
+    // can be longer than what was sent
+    let config: Config = read_config(&deps.storage)?;
+    check_field_value_limits(&config.limits, &field_values)?;
+    let normalized_field_values: Vec<SubscriptionFieldValue> =
+        normalize_field_values(&deps.api, &alert.fields, &field_values)?;
+    check_field_value_limits(&config.limits, &normalized_field_values)?;
+
     let canonical_subscriber_addr: CanonicalAddr =
         deps.api.canonical_address(&env.message.sender)?;
+    check_channels(deps, &canonical_subscriber_addr, &channels)?;
+
+    if count_subscriptions_for_address(&deps.storage, &canonical_subscriber_addr)?
+        >= config.limits.max_subscriptions_per_address
+    {
+        return Err(ContractError::TooManySubscriptions {
+            max: config.limits.max_subscriptions_per_address,
+        });
+    }
 
-    // This throws an alert if an alert with this key doesn't exist
-    // TODO: Better error message
-    read_alert(&deps.storage, &alert_key)?;
-    // This throws an error if user is not subscribed for this alert
-    // TODO: Better error message
-    read_subscription_for_address(&deps.storage, &canonical_subscriber_addr, &alert_key)?;
+    let deposit: Option<Coin> = config.limits.deposit;
+    let messages: Vec<CosmosMsg> =
+        collect_payment(deps, &env, &alert.creator, &alert.price, &deposit)?;
 
-    remove_subscription_for_address(&mut deps.storage, canonical_subscriber_addr, alert_key);
+    let expires_at: Option<u64> = match &alert.price {
+        Some(price) => Some(expiry(env.block.time, price)?),
+        None => None,
+    };
+    let subscription_id: u64 = next_subscription_id(&mut deps.storage)?;
+    let subscription: Subscription = Subscription {
+        subscription_id,
+        alert_key: alert.alert_key,
+        field_values: normalized_field_values,
+        created_at: env.block.time,
+        expires_at,
+        channels,
+        deposit,
+    };
+    let log = vec![
+        log("action", "subscribe_alert"),
+        log("alert_key", &subscription.alert_key),
+        log("subscriber", &env.message.sender),
+        log("subscription_id", subscription_id),
+        log(
+            "field_values_hash",
+            hash_field_values(&subscription.field_values)?,
+        ),
+        log("expires_at", format_expires_at(subscription.expires_at)),
+        log("deposit", format_coin(&subscription.deposit)),
+    ];
+    store_subscription_for_address(&mut deps.storage, canonical_subscriber_addr, subscription)?;
 
-    Ok(HandleResponse::default())
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: None,
+    })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
+// Subscriptions can only reference channels their subscriber has set
+fn check_channels<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetAlerts {
-            start_after,
-            limit,
-            order_by,
-        } => to_binary(&handle_query_alerts(deps, start_after, limit, order_by)),
-        QueryMsg::GetSubscriptionsForAddress {
-            subscriber_addr,
-            start_after,
-            limit,
-            order_by,
-        } => to_binary(&handle_query_subscriptions_for_address(
-            deps,
-            subscriber_addr,
-            start_after,
-            limit,
-            order_by,
-        )),
+    subscriber_addr: &CanonicalAddr,
+    channels: &[String],
+) -> Result<(), ContractError> {
+    for name in channels {
+        if may_read_channel(&deps.storage, subscriber_addr, name)?.is_none() {
+            return Err(ContractError::ChannelNotFound { name: name.clone() });
+        }
     }
+    Ok(())
 }
 
-fn handle_query_alerts<S: Storage, A: Api, Q: Querier>(
+/**
+ * Checks that the sent funds cover the price of an alert plus the deposit and pays out the price.
+ *
+ * The treasury receives its fee and the alert creator the rest (the owner for alerts without
+ * a recorded creator). The deposit stays with the contract. Everything sent on top, including
+ * coins of other denoms, is refunded to the sender.
+ */
+fn collect_payment<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
-    start_after: Option<HumanAddr>,
-    limit: Option<u32>,
-    order_by: Option<OrderBy>,
-) -> StdResult<GetAlertsResponse> {
-    let start_after = if let Some(start_after) = start_after {
-        Some(deps.api.canonical_address(&start_after)?)
-    } else {
-        None
-    };
+    env: &Env,
+    creator: &HumanAddr,
+    price: &Option<AlertPrice>,
+    deposit: &Option<Coin>,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut refund: Vec<Coin> = env
+        .message
+        .sent_funds
+        .iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .cloned()
+        .collect();
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    // Price and deposit may share a denom, the sent funds have to cover both
+    let mut required: Vec<Coin> = vec![];
+    if let Some(price) = price {
+        required.extend(coins_of(price.amount, price));
+    }
+    if let Some(deposit) = deposit {
+        match required.iter_mut().find(|coin| coin.denom == deposit.denom) {
+            Some(coin) => {
+                coin.amount = coin
+                    .amount
+                    .u128()
+                    .checked_add(deposit.amount.u128())
+                    .map(Uint128)
+                    .ok_or_else(|| ContractError::AmountOverflow {
+                        denom: deposit.denom.clone(),
+                    })?
+            }
+            None => required.push(deposit.clone()),
+        }
+    }
+    for required in required {
+        let sent: Uint128 = refund
+            .iter()
+            .find(|coin| coin.denom == required.denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_else(Uint128::zero);
+        if sent < required.amount {
+            return Err(ContractError::InsufficientFunds {
+                required: format!("{}{}", required.amount, required.denom),
+                sent: format!("{}{}", sent, required.denom),
+            });
+        }
+        for coin in refund
+            .iter_mut()
+            .filter(|coin| coin.denom == required.denom)
+        {
+            coin.amount = (coin.amount - required.amount)?;
+        }
+    }
+    refund.retain(|coin| !coin.amount.is_zero());
 
-    let alerts: Vec<Alert> = read_alerts(&deps.storage, start_after, limit, order_by)?;
+    if let Some(price) = price {
+        let config: Config = read_config(&deps.storage)?;
+        let mut creator_share: Uint128 = price.amount;
+        if let Some(treasury) = &config.treasury {
+            let treasury_share: Uint128 = price
+                .amount
+                .multiply_ratio(config.treasury_fee_percent, 100u64);
+            creator_share = (creator_share - treasury_share)?;
+            if !treasury_share.is_zero() {
+                let treasury: HumanAddr = deps.api.human_address(treasury)?;
+                messages.push(send_coins(env, treasury, coins_of(treasury_share, price)));
+            }
+        }
+        if !creator_share.is_zero() {
+            // The owner is paid for alerts without a creator and alerts of removed creators
+            let is_creator: bool = !creator.is_empty()
+                && config
+                    .creators
+                    .contains(&deps.api.canonical_address(creator)?);
+            let creator: HumanAddr = if is_creator {
+                creator.clone()
+            } else {
+                deps.api.human_address(&config.owner)?
+            };
+            messages.push(send_coins(env, creator, coins_of(creator_share, price)));
+        }
+    }
+    if !refund.is_empty() {
+        messages.push(send_coins(env, env.message.sender.clone(), refund));
+    }
 
-    Ok(GetAlertsResponse { alerts })
+    Ok(messages)
 }
 
-fn handle_query_subscriptions_for_address<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    subscriber_addr: HumanAddr,
-    start_after: Option<HumanAddr>,
-    limit: Option<u32>,
-    order_by: Option<OrderBy>,
-) -> StdResult<GetSubscriptionsForAddressResponse> {
-    let start_after = if let Some(start_after) = start_after {
-        Some(deps.api.canonical_address(&start_after)?)
-    } else {
-        None
-    };
-    let sender_addr = deps.api.canonical_address(&subscriber_addr)?;
-    let subscriptions: Vec<Subscription> =
-        read_subscriptions_for_address(&deps.storage, sender_addr, start_after, limit, order_by)?;
+// End of a billing period starting at from. Prices set before billing periods were bounded may
+// still have periods long enough to overflow
+fn expiry(from: u64, price: &AlertPrice) -> Result<u64, ContractError> {
+    from.checked_add(price.billing_period)
+        .ok_or_else(|| ContractError::InvalidPrice {
+            reason: "billing period ends after the largest block time".to_string(),
+        })
+}
 
-    Ok(GetSubscriptionsForAddressResponse { subscriptions })
+fn coins_of(amount: Uint128, price: &AlertPrice) -> Vec<Coin> {
+    vec![Coin {
+        denom: price.denom.clone(),
+        amount,
+    }]
 }
 
-#[cfg(test)]
-mod tests {
-    // use super::*;
-    // use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    // use cosmwasm_std::{coins, from_binary, StdError};
-
-    // #[test]
-    // fn proper_initialization() {
-    //     let mut deps = mock_dependencies(20, &[]);
-
-    //     let msg = InitMsg { count: 17 };
-    //     let env = mock_env("creator", &coins(1000, "earth"));
-
-    //     // we can just call .unwrap() to assert this was a success
-    //     let res = init(&mut deps, env, msg).unwrap();
-    //     assert_eq!(0, res.messages.len());
-
-    //     // it worked, let's query the state
-    //     let res = query(&deps, QueryMsg::GetCount {}).unwrap();
-    //     let value: CountResponse = from_binary(&res).unwrap();
-    //     assert_eq!(17, value.count);
-    // }
-
-    // #[test]
-    // fn increment() {
-    //     let mut deps = mock_dependencies(20, &coins(2, "token"));
-
-    //     let msg = InitMsg { count: 17 };
-    //     let env = mock_env("creator", &coins(2, "token"));
-    //     let _res = init(&mut deps, env, msg).unwrap();
-
-    //     // beneficiary can release it
-    //     let env = mock_env("anyone", &coins(2, "token"));
-    //     let msg = HandleMsg::Increment {};
-    //     let _res = handle(&mut deps, env, msg).unwrap();
-
-    //     // should increase counter by 1
-    //     let res = query(&deps, QueryMsg::GetCount {}).unwrap();
-    //     let value: CountResponse = from_binary(&res).unwrap();
-    //     assert_eq!(18, value.count);
-    // }
-
-    // #[test]
-    // fn reset() {
-    //     let mut deps = mock_dependencies(20, &coins(2, "token"));
-
-    //     let msg = InitMsg { count: 17 };
-    //     let env = mock_env("creator", &coins(2, "token"));
-    //     let _res = init(&mut deps, env, msg).unwrap();
-
-    //     // beneficiary can release it
-    //     let unauth_env = mock_env("anyone", &coins(2, "token"));
-    //     let msg = HandleMsg::Reset { count: 5 };
-    //     let res = handle(&mut deps, unauth_env, msg);
-    //     match res {
-    //         Err(StdError::Unauthorized { .. }) => {}
-    //         _ => panic!("Must return unauthorized error"),
-    //     }
-
-    //     // only the original creator can reset the counter
-    //     let auth_env = mock_env("creator", &coins(2, "token"));
-    //     let msg = HandleMsg::Reset { count: 5 };
-    //     let _res = handle(&mut deps, auth_env, msg).unwrap();
-
-    //     // should now be 5
-    //     let res = query(&deps, QueryMsg::GetCount {}).unwrap();
-    //     let value: CountResponse = from_binary(&res).unwrap();
-    //     assert_eq!(5, value.count);
-    // }
+fn send_coins(env: &Env, to_address: HumanAddr, amount: Vec<Coin>) -> CosmosMsg {
+    BankMsg::Send {
+        from_address: env.contract.address.clone(),
+        to_address,
+        amount,
+    }
+    .into()
+}
+
+pub fn try_update_subscription<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    subscription_id: u64,
+    field_values: Vec<SubscriptionFieldValue>,
+    channels: Option<Vec<String>>,
+) -> Result<HandleResponse, ContractError> {
+    let canonical_subscriber_addr: CanonicalAddr =
+        deps.api.canonical_address(&env.message.sender)?;
+    let mut subscription: Subscription = may_read_subscription_for_address(
+        &deps.storage,
+        &canonical_subscriber_addr,
+        subscription_id,
+    )?
+    .ok_or(ContractError::NotSubscribed { subscription_id })?;
+    let alert: Alert =
+        may_read_alert(&deps.storage, &subscription.alert_key)?.ok_or_else(|| {
+            ContractError::AlertNotFound {
+                alert_key: subscription.alert_key.clone(),
+            }
+        })?;
+
+    // Creation time and expiry are kept, only the values change. Limits are checked like in
+    // SubscribeAlert
+    let limits: SubscriptionLimits = read_config(&deps.storage)?.limits;
+    check_field_value_limits(&limits, &field_values)?;
+    subscription.field_values = normalize_field_values(&deps.api, &alert.fields, &field_values)?;
+    check_field_value_limits(&limits, &subscription.field_values)?;
+    if let Some(channels) = channels {
+        check_channels(deps, &canonical_subscriber_addr, &channels)?;
+        subscription.channels = channels;
+    }
+    let log = vec![
+        log("action", "update_subscription"),
+        log("alert_key", &subscription.alert_key),
+        log("subscriber", &env.message.sender),
+        log("subscription_id", subscription_id),
+        log(
+            "field_values_hash",
+            hash_field_values(&subscription.field_values)?,
+        ),
+    ];
+    store_subscription_for_address(&mut deps.storage, canonical_subscriber_addr, subscription)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+pub fn try_renew_subscription<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    subscription_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    let canonical_subscriber_addr: CanonicalAddr =
+        deps.api.canonical_address(&env.message.sender)?;
+    let mut subscription: Subscription = may_read_subscription_for_address(
+        &deps.storage,
+        &canonical_subscriber_addr,
+        subscription_id,
+    )?
+    .ok_or(ContractError::NotSubscribed { subscription_id })?;
+    let alert: Alert =
+        may_read_alert(&deps.storage, &subscription.alert_key)?.ok_or_else(|| {
+            ContractError::AlertNotFound {
+                alert_key: subscription.alert_key.clone(),
+            }
+        })?;
+    if alert.deprecated {
+        return Err(ContractError::AlertDeprecated {
+            alert_key: alert.alert_key,
+        });
+    }
+
+    let messages: Vec<CosmosMsg> =
+        collect_payment(deps, &env, &alert.creator, &alert.price, &None)?;
+
+    // Renewing a subscription to an alert that became free removes its expiry
+    subscription.expires_at = match &alert.price {
+        Some(price) => {
+            let renewed_from: u64 = match subscription.expires_at {
+                Some(expires_at) if expires_at > env.block.time => expires_at,
+                _ => env.block.time,
+            };
+            Some(expiry(renewed_from, price)?)
+        }
+        None => None,
+    };
+    let log = vec![
+        log("action", "renew_subscription"),
+        log("alert_key", &subscription.alert_key),
+        log("subscriber", &env.message.sender),
+        log("subscription_id", subscription_id),
+        log("expires_at", format_expires_at(subscription.expires_at)),
+    ];
+    store_subscription_for_address(&mut deps.storage, canonical_subscriber_addr, subscription)?;
+
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: None,
+    })
+}
+
+pub fn try_unsubscribe_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    subscription_id: u64,
+) -> Result<HandleResponse, ContractError> {
+    let canonical_subscriber_addr: CanonicalAddr =
+        deps.api.canonical_address(&env.message.sender)?;
+
+    let subscription: Subscription = may_read_subscription_for_address(
+        &deps.storage,
+        &canonical_subscriber_addr,
+        subscription_id,
+    )?
+    .ok_or(ContractError::NotSubscribed { subscription_id })?;
+
+    remove_subscription_for_address(
+        &mut deps.storage,
+        canonical_subscriber_addr,
+        subscription_id,
+    )?;
+
+    let messages: Vec<CosmosMsg> = match &subscription.deposit {
+        Some(deposit) => vec![send_coins(
+            &env,
+            env.message.sender.clone(),
+            vec![deposit.clone()],
+        )],
+        None => vec![],
+    };
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "unsubscribe_alert"),
+            log("alert_key", &subscription.alert_key),
+            log("subscriber", &env.message.sender),
+            log("subscription_id", subscription_id),
+            log("refund", format_coin(&subscription.deposit)),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_report_trigger<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alert_key: String,
+    subscriber: HumanAddr,
+    subscription_id: u64,
+    payload: String,
+    block_ref: String,
+) -> Result<HandleResponse, ContractError> {
+    let config: Config = read_config(&deps.storage)?;
+    if !config
+        .reporters
+        .contains(&deps.api.canonical_address(&env.message.sender)?)
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(ContractError::PayloadTooLong {
+            max_len: MAX_PAYLOAD_LEN,
+        });
+    }
+
+    // Only active subscriptions to the reported alert can be notified
+    let alert_key: String = resolve_alert_key(&deps.storage, alert_key)?;
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber)?;
+    let subscription: Subscription =
+        may_read_subscription_for_address(&deps.storage, &subscriber_addr, subscription_id)?
+            .filter(|subscription| subscription.alert_key == alert_key)
+            .ok_or(ContractError::NotSubscribed { subscription_id })?;
+    if subscription.is_expired(env.block.time) {
+        return Err(ContractError::SubscriptionExpired { subscription_id });
+    }
+
+    let notification: Notification = Notification {
+        notification_id: next_notification_id(&mut deps.storage)?,
+        subscription_id,
+        alert_key,
+        payload,
+        block_ref,
+        reporter: env.message.sender,
+        reported_at: env.block.time,
+        read: false,
+    };
+    store_notification(&mut deps.storage, &subscriber_addr, &notification)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "report_trigger"),
+            log("alert_key", &notification.alert_key),
+            log("subscriber", &subscriber),
+            log("subscription_id", subscription_id),
+            log("notification_id", notification.notification_id),
+            log("reporter", &notification.reporter),
+            log("block_ref", &notification.block_ref),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_ack_notifications<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    ids: Vec<u64>,
+) -> Result<HandleResponse, ContractError> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+
+    for notification_id in ids.iter().copied() {
+        let mut notification: Notification =
+            may_read_notification(&deps.storage, &subscriber_addr, notification_id)?
+                .ok_or(ContractError::NotificationNotFound { notification_id })?;
+        if !notification.read {
+            notification.read = true;
+            store_notification(&mut deps.storage, &subscriber_addr, &notification)?;
+        }
+    }
+
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "ack_notifications"),
+            log("subscriber", &env.message.sender),
+            log("notification_ids", ids.join(",")),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_set_channel<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    channel_type: ChannelType,
+    ciphertext: Binary,
+) -> Result<HandleResponse, ContractError> {
+    // Without a key nobody could have encrypted the contact handle for the notifier
+    if read_config(&deps.storage)?.notifier_pubkey.is_none() {
+        return Err(ContractError::NotifierNotSet {});
+    }
+    let channel: Channel = Channel {
+        name,
+        channel_type,
+        ciphertext,
+    };
+    validate_channel(&channel)?;
+
+    let addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    if may_read_channel(&deps.storage, &addr, &channel.name)?.is_none()
+        && read_channels(&deps.storage, &addr)?.len() >= MAX_CHANNELS
+    {
+        return Err(ContractError::InvalidChannel {
+            name: channel.name,
+            reason: format!("at most {} channels per address", MAX_CHANNELS),
+        });
+    }
+    store_channel(&mut deps.storage, &addr, &channel)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_channel"),
+            log("owner", &env.message.sender),
+            log("name", &channel.name),
+            log("channel_type", format_channel_type(&channel.channel_type)),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_remove_channel<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> Result<HandleResponse, ContractError> {
+    let addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    if may_read_channel(&deps.storage, &addr, &name)?.is_none() {
+        return Err(ContractError::ChannelNotFound { name });
+    }
+    remove_channel(&mut deps.storage, &addr, &name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "remove_channel"),
+            log("owner", &env.message.sender),
+            log("name", &name),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_add_creator<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    creator: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    let creator_addr: CanonicalAddr = deps.api.canonical_address(&creator)?;
+    if config.creators.contains(&creator_addr) {
+        return Err(ContractError::CreatorAlreadyExists {
+            creator: creator.to_string(),
+        });
+    }
+    config.creators.push(creator_addr);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "add_creator"), log("creator", &creator)],
+        data: None,
+    })
+}
+
+pub fn try_remove_creator<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    creator: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    let creator_addr: CanonicalAddr = deps.api.canonical_address(&creator)?;
+    if !config.creators.contains(&creator_addr) {
+        return Err(ContractError::CreatorNotFound {
+            creator: creator.to_string(),
+        });
+    }
+    config.creators.retain(|addr| *addr != creator_addr);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "remove_creator"), log("creator", &creator)],
+        data: None,
+    })
+}
+
+pub fn try_transfer_ownership<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    new_owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    // Only takes effect once accepted, so a mistyped address can't lock the contract
+    config.pending_owner = Some(deps.api.canonical_address(&new_owner)?);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "propose_owner"),
+            log("owner", &env.message.sender),
+            log("pending_owner", &new_owner),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_accept_ownership<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_config(&deps.storage)?;
+
+    let sender_addr: CanonicalAddr = deps.api.canonical_address(&env.message.sender)?;
+    if config.pending_owner.as_ref() != Some(&sender_addr) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let previous_owner: HumanAddr = deps.api.human_address(&config.owner)?;
+    config.owner = sender_addr;
+    config.pending_owner = None;
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "transfer_ownership"),
+            log("previous_owner", &previous_owner),
+            log("new_owner", &env.message.sender),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_add_reporter<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    reporter: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    let reporter_addr: CanonicalAddr = deps.api.canonical_address(&reporter)?;
+    if config.reporters.contains(&reporter_addr) {
+        return Err(ContractError::ReporterAlreadyExists {
+            reporter: reporter.to_string(),
+        });
+    }
+    config.reporters.push(reporter_addr);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "add_reporter"), log("reporter", &reporter)],
+        data: None,
+    })
+}
+
+pub fn try_remove_reporter<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    reporter: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    let reporter_addr: CanonicalAddr = deps.api.canonical_address(&reporter)?;
+    if !config.reporters.contains(&reporter_addr) {
+        return Err(ContractError::ReporterNotFound {
+            reporter: reporter.to_string(),
+        });
+    }
+    config.reporters.retain(|addr| *addr != reporter_addr);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "remove_reporter"), log("reporter", &reporter)],
+        data: None,
+    })
+}
+
+pub fn try_update_treasury<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    treasury: Option<HumanAddr>,
+    treasury_fee_percent: u64,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    if treasury_fee_percent > 100 {
+        return Err(ContractError::InvalidTreasuryFee {
+            fee_percent: treasury_fee_percent,
+        });
+    }
+    config.treasury = match &treasury {
+        Some(treasury) => Some(deps.api.canonical_address(treasury)?),
+        None => None,
+    };
+    config.treasury_fee_percent = treasury_fee_percent;
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "update_treasury"),
+            log(
+                "treasury",
+                treasury.map_or_else(|| NONE.to_string(), |treasury| treasury.to_string()),
+            ),
+            log("treasury_fee_percent", treasury_fee_percent),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_set_notifier_pubkey<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pubkey: Binary,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+
+    let log = vec![
+        log("action", "set_notifier_pubkey"),
+        log("pubkey", pubkey.to_base64()),
+    ];
+    config.notifier_pubkey = Some(pubkey);
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+pub fn try_update_limits<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limits: SubscriptionLimits,
+) -> Result<HandleResponse, ContractError> {
+    let mut config: Config = read_owner_config(deps, &env)?;
+    validate_subscription_limits(&limits)?;
+
+    // Existing subscriptions keep the deposit they paid, even if it changes here
+    let log = vec![
+        log("action", "update_limits"),
+        log(
+            "max_subscriptions_per_address",
+            limits.max_subscriptions_per_address,
+        ),
+        log("max_field_values", limits.max_field_values),
+        log("max_value_len", limits.max_value_len),
+        log("deposit", format_coin(&limits.deposit)),
+    ];
+    config.limits = limits;
+    store_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+// Reads the config, throwing an error if the sender is not the owner
+fn read_owner_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+) -> Result<Config, ContractError> {
+    let config: Config = read_config(&deps.storage)?;
+    if deps.api.canonical_address(&env.message.sender)? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(config)
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> Result<Binary, ContractError> {
+    // Half migrated indexes would give incomplete answers
+    if read_migration_step(&deps.storage)?.is_some() {
+        return Err(ContractError::MigrationInProgress {});
+    }
+
+    let response: Binary = match msg {
+        QueryMsg::GetAlerts {
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts(deps, start_after, limit, order_by)?)?,
+        QueryMsg::GetAlertsBy {
+            blockchain,
+            protocol,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts_by(
+            deps,
+            blockchain,
+            protocol,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetAlertsByTag {
+            tag,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts_by_tag(
+            deps,
+            tag,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetAlertsByCategory {
+            category,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts_by_category(
+            deps,
+            category,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetAlertsBySeverity {
+            severity,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts_by_severity(
+            deps,
+            severity,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::ListBlockchains {
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_blockchains(
+            deps,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::ListProtocols {
+            blockchain,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_protocols(
+            deps,
+            blockchain,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetAlert { alert_key } => to_binary(&handle_query_alert(deps, alert_key)?)?,
+        QueryMsg::GetTemplate { template_id } => {
+            to_binary(&handle_query_template(deps, template_id)?)?
+        }
+        QueryMsg::ListTemplates {
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_templates(deps, start_after, limit, order_by)?)?,
+        QueryMsg::GetAlertsByTemplate {
+            template_id,
+            outdated_only,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_alerts_by_template(
+            deps,
+            template_id,
+            outdated_only,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetSubscriptionsForAddress {
+            subscriber_addr,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_subscriptions_for_address(
+            deps,
+            subscriber_addr,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetSubscription {
+            subscriber_addr,
+            subscription_id,
+        } => to_binary(&handle_query_subscription(
+            deps,
+            subscriber_addr,
+            subscription_id,
+        )?)?,
+        QueryMsg::IsSubscribed {
+            subscriber_addr,
+            alert_key,
+            at_time,
+        } => to_binary(&handle_query_is_subscribed(
+            deps,
+            subscriber_addr,
+            alert_key,
+            at_time,
+        )?)?,
+        QueryMsg::GetSubscribersForAlert {
+            alert_key,
+            at_time,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_subscribers_for_alert(
+            deps,
+            alert_key,
+            at_time,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetNotifications {
+            subscriber,
+            unread_only,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&handle_query_notifications(
+            deps,
+            subscriber,
+            unread_only,
+            start_after,
+            limit,
+            order_by,
+        )?)?,
+        QueryMsg::GetSubscriberOverview {
+            subscriber_addr,
+            limit,
+        } => to_binary(&handle_query_subscriber_overview(
+            deps,
+            subscriber_addr,
+            limit,
+        )?)?,
+        QueryMsg::GetChannels { subscriber_addr } => {
+            to_binary(&handle_query_channels(deps, subscriber_addr)?)?
+        }
+        QueryMsg::GetNotifierPubkey {} => to_binary(&handle_query_notifier_pubkey(deps)?)?,
+        QueryMsg::GetCreators {} => to_binary(&handle_query_creators(deps)?)?,
+        QueryMsg::GetReporters {} => to_binary(&handle_query_reporters(deps)?)?,
+        QueryMsg::GetTreasury {} => to_binary(&handle_query_treasury(deps)?)?,
+        QueryMsg::GetLimits {} => to_binary(&GetLimitsResponse {
+            limits: read_config(&deps.storage)?.limits,
+        })?,
+        QueryMsg::GetKeyScheme {} => to_binary(&GetKeySchemeResponse {
+            key_scheme: read_config(&deps.storage)?.key_scheme,
+        })?,
+        QueryMsg::GetStats { subscriber_addr } => {
+            to_binary(&handle_query_stats(deps, subscriber_addr)?)?
+        }
+        QueryMsg::GetAlertStats { alert_key } => {
+            to_binary(&handle_query_alert_stats(deps, alert_key)?)?
+        }
+    };
+    Ok(response)
+}
+
+fn handle_query_alerts<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetAlertsResponse> {
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) =
+        read_alerts(&deps.storage, start_after, limit, order_by)?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_alerts_by<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    blockchain: String,
+    protocol: Option<String>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetAlertsResponse> {
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) = read_alerts_by(
+        &deps.storage,
+        &blockchain,
+        protocol.as_deref(),
+        start_after,
+        limit,
+        order_by,
+    )?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_alerts_by_tag<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    tag: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetAlertsResponse> {
+    // Tags are stored in lower case
+    let tag: String = tag.to_ascii_lowercase();
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) =
+        read_alerts_by_tag(&deps.storage, &tag, start_after, limit, order_by)?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_alerts_by_category<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    category: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetAlertsResponse> {
+    // Categories are stored in lower case, like tags
+    let category: String = category.to_ascii_lowercase();
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) =
+        read_alerts_by_category(&deps.storage, &category, start_after, limit, order_by)?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_alerts_by_severity<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    severity: Severity,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetAlertsResponse> {
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) =
+        read_alerts_by_severity(&deps.storage, &severity, start_after, limit, order_by)?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_blockchains<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<ListBlockchainsResponse> {
+    let (counts, next_cursor): (Vec<(String, u64)>, Option<String>) =
+        read_blockchain_counts(&deps.storage, start_after, limit, order_by)?;
+    let blockchains: Vec<BlockchainInfo> = counts
+        .into_iter()
+        .map(|(blockchain, alert_count)| BlockchainInfo {
+            blockchain,
+            alert_count,
+        })
+        .collect();
+
+    Ok(ListBlockchainsResponse {
+        blockchains,
+        next_cursor,
+    })
+}
+
+fn handle_query_protocols<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    blockchain: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<ListProtocolsResponse> {
+    let (counts, next_cursor): (Vec<(String, u64)>, Option<String>) =
+        read_protocol_counts(&deps.storage, &blockchain, start_after, limit, order_by)?;
+    let protocols: Vec<ProtocolInfo> = counts
+        .into_iter()
+        .map(|(protocol, alert_count)| ProtocolInfo {
+            protocol,
+            alert_count,
+        })
+        .collect();
+
+    Ok(ListProtocolsResponse {
+        protocols,
+        next_cursor,
+    })
+}
+
+fn handle_query_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    alert_key: String,
+) -> Result<GetAlertResponse, ContractError> {
+    let alert: Alert = may_read_alert(&deps.storage, &alert_key)?
+        .ok_or(ContractError::AlertNotFound { alert_key })?;
+
+    Ok(GetAlertResponse { alert })
+}
+
+fn handle_query_template<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    template_id: u64,
+) -> Result<GetTemplateResponse, ContractError> {
+    let template: AlertTemplate = may_read_template(&deps.storage, template_id)?
+        .ok_or(ContractError::TemplateNotFound { template_id })?;
+
+    Ok(GetTemplateResponse {
+        template,
+        alert_count: count_alerts_for_template(&deps.storage, template_id)?,
+    })
+}
+
+fn handle_query_templates<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<ListTemplatesResponse> {
+    let (templates, next_cursor): (Vec<AlertTemplate>, Option<u64>) =
+        read_templates(&deps.storage, start_after, limit, order_by)?;
+
+    Ok(ListTemplatesResponse {
+        templates,
+        next_cursor,
+    })
+}
+
+fn handle_query_alerts_by_template<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    template_id: u64,
+    outdated_only: Option<bool>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> Result<GetAlertsResponse, ContractError> {
+    let template: AlertTemplate = may_read_template(&deps.storage, template_id)?
+        .ok_or(ContractError::TemplateNotFound { template_id })?;
+
+    // Alerts are outdated until they have the fields of the latest template version
+    let before_version: Option<u64> = if outdated_only.unwrap_or(false) {
+        Some(template.version)
+    } else {
+        None
+    };
+    let (alerts, next_cursor): (Vec<Alert>, Option<String>) = read_alerts_by_template(
+        &deps.storage,
+        template_id,
+        before_version,
+        start_after,
+        limit,
+        order_by,
+    )?;
+
+    Ok(GetAlertsResponse {
+        alerts,
+        next_cursor,
+    })
+}
+
+fn handle_query_alert_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    alert_key: String,
+) -> Result<GetAlertStatsResponse, ContractError> {
+    let alert_key: String = match find_alert_key(&deps.storage, &alert_key)? {
+        Some(alert_key) => alert_key,
+        None => return Err(ContractError::AlertNotFound { alert_key }),
+    };
+    let (subscription_count, subscriber_count) =
+        count_subscriptions_for_alert(&deps.storage, &alert_key)?;
+
+    Ok(GetAlertStatsResponse {
+        alert_key,
+        subscription_count,
+        subscriber_count,
+    })
+}
+
+fn handle_query_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: Option<HumanAddr>,
+) -> StdResult<GetStatsResponse> {
+    let (total_alerts, total_subscriptions) = read_totals(&deps.storage)?;
+    let address_subscriptions: Option<u64> = match subscriber_addr {
+        Some(subscriber_addr) => Some(count_subscriptions_for_address(
+            &deps.storage,
+            &deps.api.canonical_address(&subscriber_addr)?,
+        )?),
+        None => None,
+    };
+
+    Ok(GetStatsResponse {
+        total_alerts,
+        total_subscriptions,
+        address_subscriptions,
+    })
+}
+
+fn handle_query_subscription<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: HumanAddr,
+    subscription_id: u64,
+) -> Result<GetSubscriptionResponse, ContractError> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber_addr)?;
+    let subscription: Subscription =
+        may_read_subscription_for_address(&deps.storage, &subscriber_addr, subscription_id)?
+            .ok_or(ContractError::NotSubscribed { subscription_id })?;
+
+    Ok(GetSubscriptionResponse { subscription })
+}
+
+fn handle_query_is_subscribed<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: HumanAddr,
+    alert_key: String,
+    at_time: u64,
+) -> StdResult<IsSubscribedResponse> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber_addr)?;
+    let alert_key: String = resolve_alert_key(&deps.storage, alert_key)?;
+    let subscription_ids: Vec<u64> =
+        read_subscriptions_for_address_and_alert(&deps.storage, &subscriber_addr, &alert_key)?
+            .into_iter()
+            .filter(|subscription| !subscription.is_expired(at_time))
+            .map(|subscription| subscription.subscription_id)
+            .collect();
+
+    Ok(IsSubscribedResponse {
+        is_subscribed: !subscription_ids.is_empty(),
+        subscription_ids,
+    })
+}
+
+fn handle_query_subscriptions_for_address<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: HumanAddr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetSubscriptionsForAddressResponse> {
+    let sender_addr = deps.api.canonical_address(&subscriber_addr)?;
+    let (subscriptions, next_cursor): (Vec<Subscription>, Option<u64>) =
+        read_subscriptions_for_address(&deps.storage, sender_addr, start_after, limit, order_by)?;
+
+    Ok(GetSubscriptionsForAddressResponse {
+        subscriptions,
+        next_cursor,
+    })
+}
+
+fn handle_query_subscribers_for_alert<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    alert_key: String,
+    at_time: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetSubscribersForAlertResponse> {
+    let alert_key: String = resolve_alert_key(&deps.storage, alert_key)?;
+    let (subscribers, next_cursor): (Vec<(CanonicalAddr, Subscription)>, Option<u64>) =
+        read_subscribers_for_alert(
+            &deps.storage,
+            &alert_key,
+            at_time,
+            start_after,
+            limit,
+            order_by,
+        )?;
+    let subscribers: Vec<AlertSubscriber> = subscribers
+        .into_iter()
+        .map(|(subscriber_addr, subscription)| {
+            Ok(AlertSubscriber {
+                subscription_id: subscription.subscription_id,
+                subscriber_addr: deps.api.human_address(&subscriber_addr)?,
+                field_values: subscription.field_values,
+                expires_at: subscription.expires_at,
+                channels: subscription.channels,
+            })
+        })
+        .collect::<StdResult<Vec<AlertSubscriber>>>()?;
+
+    Ok(GetSubscribersForAlertResponse {
+        subscribers,
+        next_cursor,
+    })
+}
+
+fn handle_query_notifications<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber: HumanAddr,
+    unread_only: Option<bool>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<GetNotificationsResponse> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber)?;
+    let (notifications, next_cursor): (Vec<Notification>, Option<u64>) = read_notifications(
+        &deps.storage,
+        &subscriber_addr,
+        unread_only.unwrap_or(false),
+        start_after,
+        limit,
+        order_by,
+    )?;
+
+    Ok(GetNotificationsResponse {
+        notifications,
+        next_cursor,
+        unread_count: count_unread_notifications(&deps.storage, &subscriber_addr)?,
+    })
+}
+
+fn handle_query_subscriber_overview<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: HumanAddr,
+    limit: Option<u32>,
+) -> StdResult<GetSubscriberOverviewResponse> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber_addr)?;
+    let (subscriptions, subscriptions_next_cursor): (Vec<Subscription>, Option<u64>) =
+        read_subscriptions_for_address(
+            &deps.storage,
+            subscriber_addr.clone(),
+            None,
+            limit,
+            Some(OrderBy::Desc),
+        )?;
+    let (notifications, notifications_next_cursor): (Vec<Notification>, Option<u64>) =
+        read_notifications(
+            &deps.storage,
+            &subscriber_addr,
+            false,
+            None,
+            limit,
+            Some(OrderBy::Desc),
+        )?;
+
+    Ok(GetSubscriberOverviewResponse {
+        subscriptions,
+        subscriptions_next_cursor,
+        notifications,
+        notifications_next_cursor,
+        unread_count: count_unread_notifications(&deps.storage, &subscriber_addr)?,
+    })
+}
+
+fn handle_query_channels<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    subscriber_addr: HumanAddr,
+) -> StdResult<GetChannelsResponse> {
+    let subscriber_addr: CanonicalAddr = deps.api.canonical_address(&subscriber_addr)?;
+    Ok(GetChannelsResponse {
+        channels: read_channels(&deps.storage, &subscriber_addr)?,
+    })
+}
+
+fn handle_query_notifier_pubkey<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<GetNotifierPubkeyResponse> {
+    Ok(GetNotifierPubkeyResponse {
+        pubkey: read_config(&deps.storage)?.notifier_pubkey,
+    })
+}
+
+fn handle_query_creators<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<GetCreatorsResponse> {
+    let config: Config = read_config(&deps.storage)?;
+    let creators: Vec<HumanAddr> = config
+        .creators
+        .iter()
+        .map(|creator| deps.api.human_address(creator))
+        .collect::<StdResult<Vec<HumanAddr>>>()?;
+
+    let pending_owner: Option<HumanAddr> = match &config.pending_owner {
+        Some(pending_owner) => Some(deps.api.human_address(pending_owner)?),
+        None => None,
+    };
+
+    Ok(GetCreatorsResponse {
+        owner: deps.api.human_address(&config.owner)?,
+        pending_owner,
+        creators,
+    })
+}
+
+fn handle_query_reporters<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<GetReportersResponse> {
+    let config: Config = read_config(&deps.storage)?;
+    let reporters: Vec<HumanAddr> = config
+        .reporters
+        .iter()
+        .map(|reporter| deps.api.human_address(reporter))
+        .collect::<StdResult<Vec<HumanAddr>>>()?;
+
+    Ok(GetReportersResponse { reporters })
+}
+
+fn handle_query_treasury<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<GetTreasuryResponse> {
+    let config: Config = read_config(&deps.storage)?;
+    let treasury: Option<HumanAddr> = match &config.treasury {
+        Some(treasury) => Some(deps.api.human_address(treasury)?),
+        None => None,
+    };
+
+    Ok(GetTreasuryResponse {
+        treasury,
+        treasury_fee_percent: config.treasury_fee_percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlertFieldType;
+    use crate::state::{read_alert, MAX_NOTIFICATIONS};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, coins, from_binary, from_slice, to_vec, Order, ReadonlyStorage};
+    use cosmwasm_storage::{to_length_prefixed, to_length_prefixed_nested};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    type MockDeps = Extern<MockStorage, MockApi, MockQuerier>;
+
+    const OWNER: &str = "owner";
+    const ALERT_KEY: &str = "terra.anchor.liquidation";
+
+    fn field(field_key: &str, field_type: AlertFieldType, optional: bool) -> AlertField {
+        AlertField {
+            field_key: field_key.to_string(),
+            name: field_key.to_string(),
+            description: String::new(),
+            field_type,
+            validation_regex: String::new(),
+            optional,
+        }
+    }
+
+    fn value(field_key: &str, value: &str) -> SubscriptionFieldValue {
+        SubscriptionFieldValue {
+            field_key: field_key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            treasury: None,
+            treasury_fee_percent: None,
+            limits: None,
+            key_scheme: None,
+        };
+        init(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        deps
+    }
+
+    fn create_alert(deps: &mut MockDeps, sender: &str, method: &str) -> HandleResponse {
+        let msg = create_alert_msg(alert_params(method));
+        handle(deps, mock_env(sender, &[]), msg).unwrap()
+    }
+
+    // A free Terra Anchor alert with a required ltv and an optional note field, tests override
+    // what they need with `..alert_params(method)`
+    fn alert_params(method: &str) -> CreateAlertParams {
+        CreateAlertParams {
+            blockchain: "Terra".to_string(),
+            protocol: "Anchor".to_string(),
+            method: method.to_string(),
+            name: "Liquidation".to_string(),
+            description: "Loan to value ratio above a threshold".to_string(),
+            fields: vec![
+                field("ltv", AlertFieldType::Percentage, false),
+                field("note", AlertFieldType::Text, true),
+            ],
+            price: None,
+            template_id: None,
+            severity: Severity::Info,
+            category: None,
+            tags: vec![],
+        }
+    }
+
+    fn create_alert_msg(params: CreateAlertParams) -> HandleMsg {
+        HandleMsg::CreateAlert {
+            blockchain: params.blockchain,
+            protocol: params.protocol,
+            method: params.method,
+            name: params.name,
+            description: params.description,
+            fields: params.fields,
+            price: params.price,
+            template_id: params.template_id,
+            severity: params.severity,
+            category: params.category,
+            tags: params.tags,
+        }
+    }
+
+    fn subscribe(
+        deps: &mut MockDeps,
+        sender: &str,
+        alert_key: &str,
+        field_values: Vec<SubscriptionFieldValue>,
+    ) -> Result<HandleResponse, ContractError> {
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: alert_key.to_string(),
+            field_values,
+            channels: vec![],
+        };
+        handle(deps, mock_env(sender, &[]), msg)
+    }
+
+    // Recipients and amounts of the bank sends of a response, in order
+    fn payouts(res: &HandleResponse) -> Vec<(String, Vec<Coin>)> {
+        res.messages
+            .iter()
+            .map(|msg| match msg {
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address, amount, ..
+                }) => (to_address.to_string(), amount.clone()),
+                msg => panic!("Unexpected message {:?}", msg),
+            })
+            .collect()
+    }
+
+    fn subscription_ids(deps: &MockDeps, subscriber: &str, order_by: OrderBy) -> Vec<u64> {
+        let mut ids: Vec<u64> = vec![];
+        let mut start_after: Option<u64> = None;
+        loop {
+            let msg = QueryMsg::GetSubscriptionsForAddress {
+                subscriber_addr: HumanAddr::from(subscriber),
+                start_after,
+                limit: Some(2),
+                order_by: Some(order_by.clone()),
+            };
+            let res: GetSubscriptionsForAddressResponse =
+                from_binary(&query(deps, msg).unwrap()).unwrap();
+            ids.extend(res.subscriptions.iter().map(|s| s.subscription_id));
+            match res.next_cursor {
+                Some(cursor) => start_after = Some(cursor),
+                None => return ids,
+            }
+        }
+    }
+
+    fn subscriber_ids(deps: &MockDeps, alert_key: &str, order_by: OrderBy) -> Vec<u64> {
+        let mut ids: Vec<u64> = vec![];
+        let mut start_after: Option<u64> = None;
+        loop {
+            let msg = QueryMsg::GetSubscribersForAlert {
+                alert_key: alert_key.to_string(),
+                at_time: mock_env(OWNER, &[]).block.time,
+                start_after,
+                limit: Some(3),
+                order_by: Some(order_by.clone()),
+            };
+            let res: GetSubscribersForAlertResponse =
+                from_binary(&query(deps, msg).unwrap()).unwrap();
+            ids.extend(res.subscribers.iter().map(|s| s.subscription_id));
+            match res.next_cursor {
+                Some(cursor) => start_after = Some(cursor),
+                None => return ids,
+            }
+        }
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let deps = setup();
+
+        let res: GetCreatorsResponse =
+            from_binary(&query(&deps, QueryMsg::GetCreators {}).unwrap()).unwrap();
+        assert_eq!(res.owner, HumanAddr::from(OWNER));
+        assert!(res.creators.is_empty());
+        assert_eq!(
+            read_contract_version(&deps.storage).unwrap(),
+            CONTRACT_VERSION
+        );
+    }
+
+    #[test]
+    fn create_alert_stores_and_indexes_it() {
+        let mut deps = setup();
+
+        let res = create_alert(&mut deps, OWNER, "liquidation");
+        assert_eq!(res.log[0], log("action", "create_alert"));
+        assert_eq!(res.log[1], log("alert_key", ALERT_KEY));
+        assert_eq!(
+            res.log[6..].to_vec(),
+            vec![
+                log("severity", "info"),
+                log("category", "none"),
+                log("tags", "none"),
+            ]
+        );
+
+        let msg = QueryMsg::GetAlert {
+            alert_key: ALERT_KEY.to_string(),
+        };
+        let res: GetAlertResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.alert.creator, HumanAddr::from(OWNER));
+        assert_eq!(res.alert.fields.len(), 2);
+
+        let msg = QueryMsg::ListProtocols {
+            blockchain: "terra".to_string(),
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        let res: ListProtocolsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.protocols[0].protocol, "anchor");
+        assert_eq!(res.protocols[0].alert_count, 1);
+    }
+
+    #[test]
+    fn blockchains_are_listed_with_alert_counts() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        create_alert(&mut deps, OWNER, "borrow");
+        let msg = create_alert_msg(CreateAlertParams {
+            blockchain: "Ethereum".to_string(),
+            protocol: "Aave".to_string(),
+            ..alert_params("liquidation")
+        });
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+
+        let list = |deps: &MockDeps, start_after: Option<&str>, order_by: OrderBy| {
+            let msg = QueryMsg::ListBlockchains {
+                start_after: start_after.map(|s| s.to_string()),
+                limit: Some(1),
+                order_by: Some(order_by),
+            };
+            let res: ListBlockchainsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            let blockchains: Vec<(String, u64)> = res
+                .blockchains
+                .into_iter()
+                .map(|info| (info.blockchain, info.alert_count))
+                .collect();
+            (blockchains, res.next_cursor)
+        };
+        assert_eq!(
+            list(&deps, None, OrderBy::Asc),
+            (
+                vec![("ethereum".to_string(), 1)],
+                Some("ethereum".to_string())
+            )
+        );
+        assert_eq!(
+            list(&deps, Some("ethereum"), OrderBy::Asc),
+            (vec![("terra".to_string(), 2)], None)
+        );
+        assert_eq!(
+            list(&deps, None, OrderBy::Desc),
+            (vec![("terra".to_string(), 2)], Some("terra".to_string()))
+        );
+
+        // A blockchain is no longer listed once its last alert is deleted
+        let msg = HandleMsg::DeleteAlert {
+            alert_key: "ethereum.aave.liquidation".to_string(),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        assert_eq!(
+            list(&deps, None, OrderBy::Asc),
+            (vec![("terra".to_string(), 2)], None)
+        );
+    }
+
+    #[test]
+    fn create_alert_rejects_duplicates() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+
+        let msg = create_alert_msg(alert_params("liquidation"));
+        match handle(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::AlertAlreadyExists { alert_key }) => {
+                assert_eq!(alert_key, ALERT_KEY)
+            }
+            res => panic!("Must return AlertAlreadyExists, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn only_owner_and_creators_manage_alerts() {
+        let mut deps = setup();
+        let msg = create_alert_msg(alert_params("liquidation"));
+
+        match handle(&mut deps, mock_env("alice", &[]), msg.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        let add_creator = HandleMsg::AddCreator {
+            creator: HumanAddr::from("alice"),
+        };
+        match handle(&mut deps, mock_env("alice", &[]), add_creator.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+
+        handle(&mut deps, mock_env(OWNER, &[]), add_creator).unwrap();
+        // Creator alerts live in the creator namespace, next to the global one
+        let res = handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert_eq!(
+            res.log[1],
+            log("alert_key", "alice/terra.anchor.liquidation")
+        );
+        create_alert(&mut deps, OWNER, "liquidation");
+
+        // Creators manage their own alerts only
+        let delete = HandleMsg::DeleteAlert {
+            alert_key: "alice/terra.anchor.liquidation".to_string(),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), delete.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        handle(&mut deps, mock_env("alice", &[]), delete).unwrap();
+    }
+
+    #[test]
+    fn key_scheme_is_set_at_init() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            treasury: None,
+            treasury_fee_percent: None,
+            limits: None,
+            key_scheme: Some(KeyScheme {
+                namespace_creators: true,
+                max_component_len: 65,
+            }),
+        };
+        match init(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::InvalidKeyScheme { .. }) => {}
+            res => panic!("Must return InvalidKeyScheme, got {:?}", res),
+        }
+
+        let key_scheme = KeyScheme {
+            namespace_creators: false,
+            max_component_len: 12,
+        };
+        let msg = InitMsg {
+            treasury: None,
+            treasury_fee_percent: None,
+            limits: None,
+            key_scheme: Some(key_scheme.clone()),
+        };
+        init(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        let res: GetKeySchemeResponse =
+            from_binary(&query(&deps, QueryMsg::GetKeyScheme {}).unwrap()).unwrap();
+        assert_eq!(res.key_scheme, key_scheme);
+
+        // Without namespaces creators share the global keys with the owner
+        let add_creator = HandleMsg::AddCreator {
+            creator: HumanAddr::from("alice"),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), add_creator).unwrap();
+        let res = create_alert(&mut deps, "alice", "liquidation");
+        assert_eq!(res.log[1], log("alert_key", ALERT_KEY));
+        let res = handle(
+            &mut deps,
+            mock_env(OWNER, &[]),
+            create_alert_msg(alert_params("liquidation")),
+        );
+        match res {
+            Err(ContractError::AlertAlreadyExists { alert_key }) => {
+                assert_eq!(alert_key, ALERT_KEY)
+            }
+            res => panic!("Must return AlertAlreadyExists, got {:?}", res),
+        }
+        match handle(
+            &mut deps,
+            mock_env(OWNER, &[]),
+            create_alert_msg(alert_params("liquidation_ratio")),
+        ) {
+            Err(ContractError::InvalidAlertKey { .. }) => {}
+            res => panic!("Must return InvalidAlertKey, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn removed_creators_lose_their_alerts() {
+        let mut deps = setup();
+        let alert_key = "alice/terra.anchor.liquidation";
+        let add_creator = HandleMsg::AddCreator {
+            creator: HumanAddr::from("alice"),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), add_creator).unwrap();
+        create_alert(&mut deps, "alice", "liquidation");
+        let set_price = HandleMsg::SetAlertPrice {
+            alert_key: alert_key.to_string(),
+            price: Some(AlertPrice {
+                denom: "uusd".to_string(),
+                amount: Uint128(100),
+                billing_period: 86400,
+            }),
+        };
+        handle(&mut deps, mock_env("alice", &[]), set_price.clone()).unwrap();
+
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: alert_key.to_string(),
+            field_values: vec![value("ltv", "75")],
+            channels: vec![],
+        };
+        let res = handle(&mut deps, mock_env("bob", &coins(100, "uusd")), msg.clone()).unwrap();
+        assert_eq!(
+            payouts(&res),
+            vec![("alice".to_string(), coins(100, "uusd"))]
+        );
+
+        let remove_creator = HandleMsg::RemoveCreator {
+            creator: HumanAddr::from("alice"),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), remove_creator).unwrap();
+        match handle(&mut deps, mock_env("alice", &[]), set_price.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        // Payments for the alert now go to the owner, who also manages it
+        let res = handle(&mut deps, mock_env("bob", &coins(100, "uusd")), msg).unwrap();
+        assert_eq!(payouts(&res), vec![(OWNER.to_string(), coins(100, "uusd"))]);
+        handle(&mut deps, mock_env(OWNER, &[]), set_price).unwrap();
+    }
+
+    #[test]
+    fn ownership_transfer_needs_acceptance() {
+        let mut deps = setup();
+        let transfer = HandleMsg::TransferOwnership {
+            new_owner: HumanAddr::from("newowner"),
+        };
+        match handle(&mut deps, mock_env("newowner", &[]), transfer.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        let res = handle(&mut deps, mock_env(OWNER, &[]), transfer).unwrap();
+        assert_eq!(res.log[0], log("action", "propose_owner"));
+
+        let res: GetCreatorsResponse =
+            from_binary(&query(&deps, QueryMsg::GetCreators {}).unwrap()).unwrap();
+        assert_eq!(res.owner, HumanAddr::from(OWNER));
+        assert_eq!(res.pending_owner, Some(HumanAddr::from("newowner")));
+
+        match handle(
+            &mut deps,
+            mock_env("mallory", &[]),
+            HandleMsg::AcceptOwnership {},
+        ) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        let res = handle(
+            &mut deps,
+            mock_env("newowner", &[]),
+            HandleMsg::AcceptOwnership {},
+        )
+        .unwrap();
+        assert_eq!(res.log[1], log("previous_owner", OWNER));
+
+        let res: GetCreatorsResponse =
+            from_binary(&query(&deps, QueryMsg::GetCreators {}).unwrap()).unwrap();
+        assert_eq!(res.owner, HumanAddr::from("newowner"));
+        assert_eq!(res.pending_owner, None);
+        let add_creator = HandleMsg::AddCreator {
+            creator: HumanAddr::from("alice"),
+        };
+        match handle(&mut deps, mock_env(OWNER, &[]), add_creator) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn deleting_an_alert_removes_subscriptions_in_batches() {
+        let mut deps = setup();
+        let alert_key = "terra.anchor.liquidation";
+        create_alert(&mut deps, OWNER, "liquidation");
+        for i in 0..MAX_DELETED_SUBSCRIPTIONS + 5 {
+            subscribe(
+                &mut deps,
+                &format!("subscriber{}", i),
+                alert_key,
+                vec![value("ltv", "75")],
+            )
+            .unwrap();
+        }
+        let delete = HandleMsg::DeleteAlert {
+            alert_key: alert_key.to_string(),
+        };
+
+        let res = handle(&mut deps, mock_env(OWNER, &[]), delete.clone()).unwrap();
+        assert_eq!(res.log[3], log("remaining_subscriptions", 5));
+        let alert: Alert = read_alert(&deps.storage, &alert_key.to_string()).unwrap();
+        assert!(alert.deprecated);
+        match subscribe(&mut deps, "latecomer", alert_key, vec![value("ltv", "75")]) {
+            Err(ContractError::AlertDeprecated { .. }) => {}
+            res => panic!("Must return AlertDeprecated, got {:?}", res),
+        }
+
+        let res = handle(&mut deps, mock_env(OWNER, &[]), delete.clone()).unwrap();
+        assert_eq!(res.log[3], log("remaining_subscriptions", 0));
+        assert_eq!(
+            count_subscriptions_for_alert(&deps.storage, &alert_key.to_string()).unwrap(),
+            (0, 0)
+        );
+        match handle(&mut deps, mock_env(OWNER, &[]), delete) {
+            Err(ContractError::AlertNotFound { .. }) => {}
+            res => panic!("Must return AlertNotFound, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn outdated_alerts_are_listed_in_bounded_pages() {
+        let mut deps = setup();
+        let msg = HandleMsg::CreateTemplate {
+            name: "Price threshold".to_string(),
+            description: String::new(),
+            fields: vec![field("threshold", AlertFieldType::Decimal, false)],
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        let methods: Vec<String> = (0..150).map(|i| format!("a{:03}", i)).collect();
+        for method in &methods {
+            let msg = create_alert_msg(CreateAlertParams {
+                protocol: "oracle".to_string(),
+                fields: vec![],
+                template_id: Some(1),
+                ..alert_params(method)
+            });
+            handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        }
+        let msg = HandleMsg::UpdateTemplate {
+            template_id: 1,
+            name: None,
+            description: None,
+            new_fields: Some(vec![field("note", AlertFieldType::Text, true)]),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        for method in methods.iter().filter(|m| *m != "a010" && *m != "a140") {
+            let msg = HandleMsg::ApplyTemplate {
+                alert_key: format!("terra.oracle.{}", method),
+            };
+            handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        }
+
+        // Each page reads at most 100 alerts, the first one ends where its scan stopped
+        let mut pages: Vec<(Vec<String>, Option<String>)> = vec![];
+        let mut start_after: Option<String> = None;
+        loop {
+            let msg = QueryMsg::GetAlertsByTemplate {
+                template_id: 1,
+                outdated_only: Some(true),
+                start_after,
+                limit: Some(5),
+                order_by: Some(OrderBy::Asc),
+            };
+            let res: GetAlertsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+            let alert_keys: Vec<String> = res.alerts.into_iter().map(|a| a.alert_key).collect();
+            pages.push((alert_keys, res.next_cursor.clone()));
+            start_after = res.next_cursor;
+            if start_after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(
+            pages,
+            vec![
+                (
+                    vec!["terra.oracle.a010".to_string()],
+                    Some("terra.oracle.a099".to_string())
+                ),
+                (vec!["terra.oracle.a140".to_string()], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn alerts_inherit_template_fields() {
+        let mut deps = setup();
+        let msg = HandleMsg::CreateTemplate {
+            name: "Price threshold".to_string(),
+            description: "Price crosses a threshold".to_string(),
+            fields: vec![
+                field("threshold", AlertFieldType::Decimal, false),
+                field("note", AlertFieldType::Text, true),
+            ],
+        };
+        let res = handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.log[1], log("template_id", 1));
+
+        let create = |deps: &mut MockDeps, method: &str| {
+            let msg = create_alert_msg(CreateAlertParams {
+                protocol: "oracle".to_string(),
+                name: method.to_string(),
+                description: String::new(),
+                fields: vec![field("threshold", AlertFieldType::Uint128, false)],
+                template_id: Some(1),
+                ..alert_params(method)
+            });
+            handle(deps, mock_env(OWNER, &[]), msg).unwrap()
+        };
+        let res = create(&mut deps, "luna");
+        assert_eq!(res.log[5], log("template_id", 1));
+        create(&mut deps, "ust");
+
+        let alert: Alert = read_alert(&deps.storage, &"terra.oracle.luna".to_string()).unwrap();
+        assert_eq!(alert.description, "Price crosses a threshold");
+        assert_eq!(
+            alert.fields,
+            vec![
+                field("threshold", AlertFieldType::Uint128, false),
+                field("note", AlertFieldType::Text, true),
+            ]
+        );
+
+        // Template changes are reported with the number of derived alerts
+        let msg = HandleMsg::UpdateTemplate {
+            template_id: 1,
+            name: None,
+            description: None,
+            new_fields: Some(vec![field("direction", AlertFieldType::Text, true)]),
+        };
+        let res = handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        assert_eq!(res.log[3], log("version", 2));
+        assert_eq!(res.log[5], log("alert_count", 2));
+
+        let outdated = |deps: &MockDeps| -> Vec<String> {
+            let msg = QueryMsg::GetAlertsByTemplate {
+                template_id: 1,
+                outdated_only: Some(true),
+                start_after: None,
+                limit: None,
+                order_by: Some(OrderBy::Asc),
+            };
+            let res: GetAlertsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            res.alerts
+                .into_iter()
+                .map(|alert| alert.alert_key)
+                .collect()
+        };
+        assert_eq!(
+            outdated(&deps),
+            vec!["terra.oracle.luna", "terra.oracle.ust"]
+        );
+
+        let msg = HandleMsg::ApplyTemplate {
+            alert_key: "terra.oracle.luna".to_string(),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        let alert: Alert = read_alert(&deps.storage, &"terra.oracle.luna".to_string()).unwrap();
+        assert_eq!(alert.fields.len(), 3);
+        assert_eq!(alert.fields[0].field_type, AlertFieldType::Uint128);
+        assert_eq!(outdated(&deps), vec!["terra.oracle.ust"]);
+
+        match handle(
+            &mut deps,
+            mock_env(OWNER, &[]),
+            HandleMsg::DeleteTemplate { template_id: 1 },
+        ) {
+            Err(ContractError::TemplateInUse { alert_count, .. }) => assert_eq!(alert_count, 2),
+            res => panic!("Must return TemplateInUse, got {:?}", res),
+        }
+        for method in &["luna", "ust"] {
+            let msg = HandleMsg::DeleteAlert {
+                alert_key: format!("terra.oracle.{}", method),
+            };
+            handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        }
+        let res: GetTemplateResponse =
+            from_binary(&query(&deps, QueryMsg::GetTemplate { template_id: 1 }).unwrap()).unwrap();
+        assert_eq!(res.alert_count, 0);
+        handle(
+            &mut deps,
+            mock_env(OWNER, &[]),
+            HandleMsg::DeleteTemplate { template_id: 1 },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn alerts_are_found_by_tag() {
+        let mut deps = setup();
+        for (protocol, method, tags) in &[
+            ("anchor", "liquidation", vec!["Liquidation", "lending"]),
+            ("mirror", "liquidation", vec!["liquidation"]),
+            ("mirror", "price", vec!["oracle"]),
+        ] {
+            let msg = create_alert_msg(CreateAlertParams {
+                protocol: protocol.to_string(),
+                severity: Severity::Critical,
+                category: Some("DeFi".to_string()),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..alert_params(method)
+            });
+            handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        }
+        let alert: Alert =
+            read_alert(&deps.storage, &"terra.anchor.liquidation".to_string()).unwrap();
+        assert_eq!(alert.severity, Severity::Critical);
+        assert_eq!(alert.category, Some("defi".to_string()));
+        assert_eq!(alert.tags, vec!["liquidation", "lending"]);
+
+        let tagged = |deps: &MockDeps, tag: &str| -> Vec<String> {
+            let mut alert_keys: Vec<String> = vec![];
+            let mut start_after: Option<String> = None;
+            loop {
+                let msg = QueryMsg::GetAlertsByTag {
+                    tag: tag.to_string(),
+                    start_after,
+                    limit: Some(1),
+                    order_by: Some(OrderBy::Asc),
+                };
+                let res: GetAlertsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+                alert_keys.extend(res.alerts.into_iter().map(|alert| alert.alert_key));
+                match res.next_cursor {
+                    Some(cursor) => start_after = Some(cursor),
+                    None => return alert_keys,
+                }
+            }
+        };
+        assert_eq!(
+            tagged(&deps, "LIQUIDATION"),
+            vec!["terra.anchor.liquidation", "terra.mirror.liquidation"]
+        );
+        let labelled = |deps: &MockDeps, msg: QueryMsg| -> usize {
+            let res: GetAlertsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            res.alerts.len()
+        };
+        let by_category = |category: &str| QueryMsg::GetAlertsByCategory {
+            category: category.to_string(),
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        let by_severity = |severity: Severity| QueryMsg::GetAlertsBySeverity {
+            severity,
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        assert_eq!(labelled(&deps, by_category("DEFI")), 3);
+        assert_eq!(labelled(&deps, by_severity(Severity::Critical)), 3);
+
+        // Replacing the tags moves the alert between indexes
+        let msg = HandleMsg::UpdateAlert {
+            alert_key: "terra.mirror.price".to_string(),
+            name: None,
+            description: None,
+            new_fields: None,
+            severity: Some(Severity::Warning),
+            category: Some(String::new()),
+            tags: Some(vec!["liquidation".to_string()]),
+        };
+        let res = handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        assert_eq!(
+            res.log[4..].to_vec(),
+            vec![
+                log("severity", "warning"),
+                log("category", "none"),
+                log("tags", "liquidation"),
+            ]
+        );
+        assert!(tagged(&deps, "oracle").is_empty());
+        assert_eq!(tagged(&deps, "liquidation").len(), 3);
+        let alert: Alert = read_alert(&deps.storage, &"terra.mirror.price".to_string()).unwrap();
+        assert_eq!(alert.severity, Severity::Warning);
+        assert_eq!(alert.category, None);
+        assert_eq!(labelled(&deps, by_category("defi")), 2);
+        assert_eq!(labelled(&deps, by_severity(Severity::Critical)), 2);
+        assert_eq!(labelled(&deps, by_severity(Severity::Warning)), 1);
+
+        let msg = HandleMsg::DeleteAlert {
+            alert_key: "terra.anchor.liquidation".to_string(),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        assert!(tagged(&deps, "lending").is_empty());
+
+        let msg = create_alert_msg(CreateAlertParams {
+            tags: vec!["price feed".to_string()],
+            ..alert_params("borrow")
+        });
+        match handle(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::InvalidTag { tag, .. }) => assert_eq!(tag, "price feed"),
+            res => panic!("Must return InvalidTag, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn subscribe_alert() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+
+        let res = subscribe(&mut deps, "alice", ALERT_KEY, vec![value("ltv", "75.0")]).unwrap();
+        assert_eq!(res.log[0], log("action", "subscribe_alert"));
+        assert_eq!(res.log[3], log("subscription_id", 1));
+
+        let msg = QueryMsg::GetSubscription {
+            subscriber_addr: HumanAddr::from("alice"),
+            subscription_id: 1,
+        };
+        let res: GetSubscriptionResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.subscription.alert_key, ALERT_KEY);
+        // Values are normalized and ordered like the alert fields
+        assert_eq!(res.subscription.field_values, vec![value("ltv", "75")]);
+
+        let msg = QueryMsg::IsSubscribed {
+            subscriber_addr: HumanAddr::from("alice"),
+            alert_key: ALERT_KEY.to_string(),
+            at_time: mock_env(OWNER, &[]).block.time,
+        };
+        let res: IsSubscribedResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert!(res.is_subscribed);
+        assert_eq!(res.subscription_ids, vec![1]);
+    }
+
+    // A contract taking a 10% treasury fee and a 10uusd deposit, with a paid alert
+    fn setup_paid_alert(price: u128) -> MockDeps {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            treasury: Some(HumanAddr::from("treasury")),
+            treasury_fee_percent: Some(10),
+            limits: Some(SubscriptionLimits {
+                deposit: Some(coin(10, "uusd")),
+                ..SubscriptionLimits::default()
+            }),
+            key_scheme: None,
+        };
+        init(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        create_alert(&mut deps, OWNER, "liquidation");
+        let msg = HandleMsg::SetAlertPrice {
+            alert_key: "terra.anchor.liquidation".to_string(),
+            price: Some(AlertPrice {
+                denom: "uusd".to_string(),
+                amount: Uint128(price),
+                billing_period: 86400,
+            }),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        deps
+    }
+
+    fn subscribe_with(
+        deps: &mut MockDeps,
+        funds: &[Coin],
+    ) -> Result<HandleResponse, ContractError> {
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: "terra.anchor.liquidation".to_string(),
+            field_values: vec![value("ltv", "75")],
+            channels: vec![],
+        };
+        handle(deps, mock_env("bob", funds), msg)
+    }
+
+    #[test]
+    fn init_rejects_invalid_config() {
+        let mut deps = mock_dependencies(20, &[]);
+        let msg = InitMsg {
+            treasury: None,
+            treasury_fee_percent: Some(101),
+            limits: None,
+            key_scheme: None,
+        };
+        match init(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::InvalidTreasuryFee { fee_percent }) => assert_eq!(fee_percent, 101),
+            res => panic!("Must return InvalidTreasuryFee, got {:?}", res),
+        }
+        let msg = InitMsg {
+            treasury: None,
+            treasury_fee_percent: None,
+            limits: Some(SubscriptionLimits {
+                max_value_len: 0,
+                ..SubscriptionLimits::default()
+            }),
+            key_scheme: None,
+        };
+        match init(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::InvalidLimits { .. }) => {}
+            res => panic!("Must return InvalidLimits, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn owner_updates_the_limits() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        let limits = |deps: &MockDeps| -> SubscriptionLimits {
+            let res: GetLimitsResponse =
+                from_binary(&query(deps, QueryMsg::GetLimits {}).unwrap()).unwrap();
+            res.limits
+        };
+        assert_eq!(limits(&deps), SubscriptionLimits::default());
+
+        let new_limits = SubscriptionLimits {
+            max_subscriptions_per_address: 1,
+            deposit: Some(coin(5, "uusd")),
+            ..SubscriptionLimits::default()
+        };
+        let update = |limits: &SubscriptionLimits| HandleMsg::UpdateLimits {
+            limits: limits.clone(),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), update(&new_limits)) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        let invalid = SubscriptionLimits {
+            max_field_values: 0,
+            ..SubscriptionLimits::default()
+        };
+        match handle(&mut deps, mock_env(OWNER, &[]), update(&invalid)) {
+            Err(ContractError::InvalidLimits { .. }) => {}
+            res => panic!("Must return InvalidLimits, got {:?}", res),
+        }
+        assert_eq!(limits(&deps), SubscriptionLimits::default());
+
+        let res = handle(&mut deps, mock_env(OWNER, &[]), update(&new_limits)).unwrap();
+        assert_eq!(res.log[4], log("deposit", "5uusd"));
+        assert_eq!(limits(&deps), new_limits);
+
+        // New subscriptions are checked against the updated limits
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: ALERT_KEY.to_string(),
+            field_values: vec![value("ltv", "75")],
+            channels: vec![],
+        };
+        handle(&mut deps, mock_env("bob", &coins(5, "uusd")), msg.clone()).unwrap();
+        match handle(&mut deps, mock_env("bob", &coins(5, "uusd")), msg) {
+            Err(ContractError::TooManySubscriptions { max }) => assert_eq!(max, 1),
+            res => panic!("Must return TooManySubscriptions, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn payments_are_split_and_overpayments_refunded() {
+        let mut deps = setup_paid_alert(100);
+
+        // Price and deposit share the denom, the deposit stays in the contract
+        let res = subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        assert_eq!(
+            payouts(&res),
+            vec![
+                ("treasury".to_string(), coins(10, "uusd")),
+                (OWNER.to_string(), coins(90, "uusd")),
+            ]
+        );
+
+        let res = subscribe_with(&mut deps, &[coin(150, "uusd"), coin(5, "uluna")]).unwrap();
+        assert_eq!(
+            payouts(&res),
+            vec![
+                ("treasury".to_string(), coins(10, "uusd")),
+                (OWNER.to_string(), coins(90, "uusd")),
+                ("bob".to_string(), vec![coin(40, "uusd"), coin(5, "uluna")]),
+            ]
+        );
+
+        // Subscriptions are only stored when paid for
+        match subscribe_with(&mut deps, &[coin(109, "uusd"), coin(200, "uluna")]) {
+            Err(ContractError::InsufficientFunds { required, sent }) => {
+                assert_eq!(required, "110uusd");
+                assert_eq!(sent, "109uusd");
+            }
+            res => panic!("Must return InsufficientFunds, got {:?}", res),
+        }
+        assert_eq!(
+            count_subscriptions_for_alert(&deps.storage, &"terra.anchor.liquidation".to_string())
+                .unwrap(),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn payments_reject_overflowing_amounts() {
+        let mut deps = setup_paid_alert(u128::MAX - 5);
+        match subscribe_with(&mut deps, &coins(u128::MAX, "uusd")) {
+            Err(ContractError::AmountOverflow { denom }) => assert_eq!(denom, "uusd"),
+            res => panic!("Must return AmountOverflow, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn owner_updates_the_treasury() {
+        let mut deps = setup_paid_alert(100);
+        let update =
+            |treasury: Option<&str>, treasury_fee_percent: u64| HandleMsg::UpdateTreasury {
+                treasury: treasury.map(HumanAddr::from),
+                treasury_fee_percent,
+            };
+        let treasury = |deps: &MockDeps| -> GetTreasuryResponse {
+            from_binary(&query(deps, QueryMsg::GetTreasury {}).unwrap()).unwrap()
+        };
+        assert_eq!(
+            treasury(&deps),
+            GetTreasuryResponse {
+                treasury: Some(HumanAddr::from("treasury")),
+                treasury_fee_percent: 10,
+            }
+        );
+
+        match handle(&mut deps, mock_env("bob", &[]), update(Some("bob"), 100)) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        match handle(&mut deps, mock_env(OWNER, &[]), update(Some("vault"), 101)) {
+            Err(ContractError::InvalidTreasuryFee { fee_percent }) => assert_eq!(fee_percent, 101),
+            res => panic!("Must return InvalidTreasuryFee, got {:?}", res),
+        }
+        assert_eq!(treasury(&deps).treasury_fee_percent, 10);
+
+        let res = handle(&mut deps, mock_env(OWNER, &[]), update(Some("vault"), 25)).unwrap();
+        assert_eq!(res.log[1], log("treasury", "vault"));
+        assert_eq!(
+            treasury(&deps),
+            GetTreasuryResponse {
+                treasury: Some(HumanAddr::from("vault")),
+                treasury_fee_percent: 25,
+            }
+        );
+        let res = subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        assert_eq!(
+            payouts(&res),
+            vec![
+                ("vault".to_string(), coins(25, "uusd")),
+                (OWNER.to_string(), coins(75, "uusd")),
+            ]
+        );
+
+        // Without a treasury the creator is paid in full
+        handle(&mut deps, mock_env(OWNER, &[]), update(None, 25)).unwrap();
+        assert_eq!(treasury(&deps).treasury, None);
+        let res = subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        assert_eq!(payouts(&res), vec![(OWNER.to_string(), coins(100, "uusd"))]);
+    }
+
+    fn is_subscribed(deps: &MockDeps, at_time: u64) -> Vec<u64> {
+        let msg = QueryMsg::IsSubscribed {
+            subscriber_addr: HumanAddr::from("bob"),
+            alert_key: "terra.anchor.liquidation".to_string(),
+            at_time,
+        };
+        let res: IsSubscribedResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+        assert_eq!(res.is_subscribed, !res.subscription_ids.is_empty());
+        res.subscription_ids
+    }
+
+    fn renew(deps: &mut MockDeps, time: u64) -> Result<Option<u64>, ContractError> {
+        let mut env = mock_env("bob", &coins(100, "uusd"));
+        env.block.time = time;
+        handle(
+            deps,
+            env,
+            HandleMsg::RenewSubscription { subscription_id: 1 },
+        )?;
+        Ok(bobs_subscription(deps, 1).expires_at)
+    }
+
+    fn bobs_subscription(deps: &MockDeps, subscription_id: u64) -> Subscription {
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        may_read_subscription_for_address(&deps.storage, &bob, subscription_id)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn update_subscription_changes_values_in_place() {
+        let mut deps = setup_paid_alert(100);
+        subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        let subscribed: Subscription = bobs_subscription(&deps, 1);
+        let update = |field_values: Vec<SubscriptionFieldValue>| HandleMsg::UpdateSubscription {
+            subscription_id: 1,
+            field_values,
+            channels: None,
+        };
+
+        let msg = update(vec![value("note", "hi"), value("ltv", "080")]);
+        let res = handle(&mut deps, mock_env("bob", &[]), msg.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.log[0], log("action", "update_subscription"));
+        let updated: Subscription = bobs_subscription(&deps, 1);
+        assert_eq!(
+            updated.field_values,
+            vec![value("ltv", "80"), value("note", "hi")]
+        );
+        assert_eq!(updated.created_at, subscribed.created_at);
+        assert_eq!(updated.expires_at, subscribed.expires_at);
+        assert_eq!(updated.deposit, Some(coin(10, "uusd")));
+
+        match handle(&mut deps, mock_env("carol", &[]), msg) {
+            Err(ContractError::NotSubscribed { subscription_id }) => assert_eq!(subscription_id, 1),
+            res => panic!("Must return NotSubscribed, got {:?}", res),
+        }
+        match handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            update(vec![value("ltv", "75"), value("ratio", "1")]),
+        ) {
+            Err(ContractError::UnknownField { field_key }) => assert_eq!(field_key, "ratio"),
+            res => panic!("Must return UnknownField, got {:?}", res),
+        }
+        let msg = HandleMsg::UpdateSubscription {
+            subscription_id: 1,
+            field_values: vec![value("ltv", "75")],
+            channels: Some(vec!["telegram".to_string()]),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), msg) {
+            Err(ContractError::ChannelNotFound { name }) => assert_eq!(name, "telegram"),
+            res => panic!("Must return ChannelNotFound, got {:?}", res),
+        }
+        assert_eq!(bobs_subscription(&deps, 1), updated);
+
+        // Updates are checked against the current limits, the deposit stays what was paid
+        let msg = HandleMsg::UpdateLimits {
+            limits: SubscriptionLimits {
+                max_value_len: 3,
+                deposit: Some(coin(20, "uusd")),
+                ..SubscriptionLimits::default()
+            },
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        match handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            update(vec![value("ltv", "75"), value("note", "long")]),
+        ) {
+            Err(ContractError::FieldValueTooLong { field_key, max_len }) => {
+                assert_eq!(field_key, "note");
+                assert_eq!(max_len, 3);
+            }
+            res => panic!("Must return FieldValueTooLong, got {:?}", res),
+        }
+        handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            update(vec![value("ltv", "75")]),
+        )
+        .unwrap();
+        assert_eq!(bobs_subscription(&deps, 1).deposit, Some(coin(10, "uusd")));
+        let res = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::UnsubscribeAlert { subscription_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(payouts(&res), vec![("bob".to_string(), coins(10, "uusd"))]);
+    }
+
+    #[test]
+    fn subscriptions_expire_and_renew() {
+        const DAY: u64 = 86400;
+        let mut deps = setup_paid_alert(100);
+        let start: u64 = mock_env("bob", &[]).block.time;
+        subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        assert_eq!(is_subscribed(&deps, start + DAY - 1), vec![1]);
+        assert!(is_subscribed(&deps, start + DAY).is_empty());
+        let msg = QueryMsg::GetSubscribersForAlert {
+            alert_key: "terra.anchor.liquidation".to_string(),
+            at_time: start + DAY,
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        let res: GetSubscribersForAlertResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert!(res.subscribers.is_empty());
+
+        // An active subscription is extended, an expired one restarts at the renewal
+        assert_eq!(
+            renew(&mut deps, start + 100).unwrap(),
+            Some(start + 2 * DAY)
+        );
+        assert_eq!(
+            renew(&mut deps, start + 3 * DAY).unwrap(),
+            Some(start + 4 * DAY)
+        );
+        assert_eq!(is_subscribed(&deps, start + 4 * DAY - 1), vec![1]);
+
+        // Expired subscriptions don't keep their owner from subscribing again
+        let mut env = mock_env("bob", &coins(110, "uusd"));
+        env.block.time = start + 5 * DAY;
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: "terra.anchor.liquidation".to_string(),
+            field_values: vec![value("ltv", "75")],
+            channels: vec![],
+        };
+        handle(&mut deps, env, msg).unwrap();
+        assert_eq!(is_subscribed(&deps, start + 5 * DAY), vec![2]);
+    }
+
+    #[test]
+    fn subscriber_pages_skip_expired_subscriptions_in_bounded_scans() {
+        let mut deps = setup_paid_alert(100);
+        let subscribe_at = |deps: &mut MockDeps, subscriber: &str, time: u64| {
+            let mut env = mock_env(subscriber, &coins(110, "uusd"));
+            env.block.time = time;
+            let msg = HandleMsg::SubscribeAlert {
+                alert_key: ALERT_KEY.to_string(),
+                field_values: vec![value("ltv", "75")],
+                channels: vec![],
+            };
+            handle(deps, env, msg).unwrap();
+        };
+        for i in 0..101 {
+            subscribe_at(&mut deps, &format!("sub{:03}", i), 1000);
+        }
+        subscribe_at(&mut deps, "bob", 1000 + 2 * 86400);
+
+        // The first page reads 100 expired subscriptions and stops there
+        let mut pages: Vec<(Vec<u64>, Option<u64>)> = vec![];
+        let mut start_after: Option<u64> = None;
+        loop {
+            let msg = QueryMsg::GetSubscribersForAlert {
+                alert_key: ALERT_KEY.to_string(),
+                at_time: 1000 + 86400 + 1,
+                start_after,
+                limit: None,
+                order_by: Some(OrderBy::Asc),
+            };
+            let res: GetSubscribersForAlertResponse =
+                from_binary(&query(&deps, msg).unwrap()).unwrap();
+            let ids: Vec<u64> = res.subscribers.iter().map(|s| s.subscription_id).collect();
+            pages.push((ids, res.next_cursor));
+            start_after = res.next_cursor;
+            if start_after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, vec![(vec![], Some(100)), (vec![102], None)]);
+    }
+
+    #[test]
+    fn billing_periods_cannot_overflow_expiry() {
+        let mut deps = setup_paid_alert(100);
+        let alert_key = "terra.anchor.liquidation".to_string();
+        let msg = HandleMsg::SetAlertPrice {
+            alert_key: alert_key.clone(),
+            price: Some(AlertPrice {
+                denom: "uusd".to_string(),
+                amount: Uint128(100),
+                billing_period: u64::MAX,
+            }),
+        };
+        match handle(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::InvalidPrice { .. }) => {}
+            res => panic!("Must return InvalidPrice, got {:?}", res),
+        }
+
+        // Prices stored before billing periods were bounded
+        subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        let mut alert: Alert = read_alert(&deps.storage, &alert_key).unwrap();
+        alert.price.as_mut().unwrap().billing_period = u64::MAX;
+        store_alert(&mut deps.storage, &alert).unwrap();
+        match renew(&mut deps, mock_env("bob", &[]).block.time) {
+            Err(ContractError::InvalidPrice { .. }) => {}
+            res => panic!("Must return InvalidPrice, got {:?}", res),
+        }
+        match subscribe_with(&mut deps, &coins(110, "uusd")) {
+            Err(ContractError::InvalidPrice { .. }) => {}
+            res => panic!("Must return InvalidPrice, got {:?}", res),
+        }
+    }
+
+    fn report_trigger(alert_key: &str, subscriber: &str, subscription_id: u64) -> HandleMsg {
+        HandleMsg::ReportTrigger {
+            alert_key: alert_key.to_string(),
+            subscriber: HumanAddr::from(subscriber),
+            subscription_id,
+            payload: "ltv at 80%".to_string(),
+            block_ref: "1234".to_string(),
+        }
+    }
+
+    fn add_reporter(deps: &mut MockDeps, reporter: &str) {
+        let msg = HandleMsg::AddReporter {
+            reporter: HumanAddr::from(reporter),
+        };
+        handle(deps, mock_env(OWNER, &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn owner_manages_reporters() {
+        let mut deps = setup();
+        let reporters = |deps: &MockDeps| -> Vec<HumanAddr> {
+            let res: GetReportersResponse =
+                from_binary(&query(deps, QueryMsg::GetReporters {}).unwrap()).unwrap();
+            res.reporters
+        };
+        assert!(reporters(&deps).is_empty());
+
+        add_reporter(&mut deps, "reporter");
+        add_reporter(&mut deps, "backup");
+        assert_eq!(
+            reporters(&deps),
+            vec![HumanAddr::from("reporter"), HumanAddr::from("backup")]
+        );
+
+        let add = HandleMsg::AddReporter {
+            reporter: HumanAddr::from("reporter"),
+        };
+        match handle(&mut deps, mock_env(OWNER, &[]), add.clone()) {
+            Err(ContractError::ReporterAlreadyExists { reporter }) => {
+                assert_eq!(reporter, "reporter")
+            }
+            res => panic!("Must return ReporterAlreadyExists, got {:?}", res),
+        }
+        let remove = HandleMsg::RemoveReporter {
+            reporter: HumanAddr::from("reporter"),
+        };
+        for msg in &[add, remove.clone()] {
+            match handle(&mut deps, mock_env("reporter", &[]), msg.clone()) {
+                Err(ContractError::Unauthorized {}) => {}
+                res => panic!("Must return Unauthorized, got {:?}", res),
+            }
+        }
+
+        let res = handle(&mut deps, mock_env(OWNER, &[]), remove.clone()).unwrap();
+        assert_eq!(res.log[1], log("reporter", "reporter"));
+        assert_eq!(reporters(&deps), vec![HumanAddr::from("backup")]);
+        match handle(&mut deps, mock_env(OWNER, &[]), remove) {
+            Err(ContractError::ReporterNotFound { reporter }) => assert_eq!(reporter, "reporter"),
+            res => panic!("Must return ReporterNotFound, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn report_trigger_checks_reporter_and_subscription() {
+        let mut deps = setup_paid_alert(100);
+        subscribe_with(&mut deps, &coins(110, "uusd")).unwrap();
+        create_alert(&mut deps, OWNER, "borrow");
+        let alert_key = "terra.anchor.liquidation";
+
+        match handle(
+            &mut deps,
+            mock_env("reporter", &[]),
+            report_trigger(alert_key, "bob", 1),
+        ) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        add_reporter(&mut deps, "reporter");
+
+        // The subscription has to belong to the subscriber and to the reported alert
+        for msg in &[
+            report_trigger(alert_key, "bob", 2),
+            report_trigger(alert_key, "carol", 1),
+            report_trigger("terra.anchor.borrow", "bob", 1),
+        ] {
+            match handle(&mut deps, mock_env("reporter", &[]), msg.clone()) {
+                Err(ContractError::NotSubscribed { .. }) => {}
+                res => panic!("Must return NotSubscribed, got {:?}", res),
+            }
+        }
+        let msg = HandleMsg::ReportTrigger {
+            alert_key: alert_key.to_string(),
+            subscriber: HumanAddr::from("bob"),
+            subscription_id: 1,
+            payload: "x".repeat(MAX_PAYLOAD_LEN + 1),
+            block_ref: "1234".to_string(),
+        };
+        match handle(&mut deps, mock_env("reporter", &[]), msg) {
+            Err(ContractError::PayloadTooLong { max_len }) => assert_eq!(max_len, MAX_PAYLOAD_LEN),
+            res => panic!("Must return PayloadTooLong, got {:?}", res),
+        }
+
+        let res = handle(
+            &mut deps,
+            mock_env("reporter", &[]),
+            report_trigger(alert_key, "bob", 1),
+        )
+        .unwrap();
+        assert_eq!(res.log[4], log("notification_id", 1));
+        assert_eq!(res.log[5], log("reporter", "reporter"));
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        let notification: Notification = may_read_notification(&deps.storage, &bob, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(notification.payload, "ltv at 80%");
+        assert!(!notification.read);
+
+        let mut env = mock_env("reporter", &[]);
+        env.block.time = bobs_subscription(&deps, 1).expires_at.unwrap();
+        match handle(&mut deps, env, report_trigger(alert_key, "bob", 1)) {
+            Err(ContractError::SubscriptionExpired { subscription_id }) => {
+                assert_eq!(subscription_id, 1)
+            }
+            res => panic!("Must return SubscriptionExpired, got {:?}", res),
+        }
+
+        let msg = HandleMsg::RemoveReporter {
+            reporter: HumanAddr::from("reporter"),
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        match handle(
+            &mut deps,
+            mock_env("reporter", &[]),
+            report_trigger(alert_key, "bob", 1),
+        ) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+    }
+
+    fn notifications(deps: &MockDeps, unread_only: bool) -> GetNotificationsResponse {
+        let msg = QueryMsg::GetNotifications {
+            subscriber: HumanAddr::from("bob"),
+            unread_only: Some(unread_only),
+            start_after: None,
+            limit: Some(5),
+            order_by: Some(OrderBy::Asc),
+        };
+        from_binary(&query(deps, msg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn notifications_are_pruned_and_acked() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "75")]).unwrap();
+        add_reporter(&mut deps, "reporter");
+        let report = |deps: &mut MockDeps| {
+            handle(
+                deps,
+                mock_env("reporter", &[]),
+                report_trigger(ALERT_KEY, "bob", 1),
+            )
+            .unwrap();
+        };
+        for _ in 0..MAX_NOTIFICATIONS + 5 {
+            report(&mut deps);
+        }
+
+        // The oldest notifications were pruned along with their unread entries
+        let res = notifications(&deps, false);
+        let ids: Vec<u64> = res
+            .notifications
+            .iter()
+            .map(|n| n.notification_id)
+            .collect();
+        assert_eq!(ids, vec![6, 7, 8, 9, 10]);
+        assert_eq!(res.unread_count, MAX_NOTIFICATIONS);
+
+        let ack = |ids: Vec<u64>| HandleMsg::AckNotifications { ids };
+        let res = handle(&mut deps, mock_env("bob", &[]), ack(vec![6, 8])).unwrap();
+        assert_eq!(res.log[2], log("notification_ids", "6,8"));
+        handle(&mut deps, mock_env("bob", &[]), ack(vec![8])).unwrap();
+        let res = notifications(&deps, true);
+        let ids: Vec<u64> = res
+            .notifications
+            .iter()
+            .map(|n| n.notification_id)
+            .collect();
+        assert_eq!(ids, vec![7, 9, 10, 11, 12]);
+        assert_eq!(res.unread_count, MAX_NOTIFICATIONS - 2);
+        assert!(notifications(&deps, false).notifications[0].read);
+
+        // Only existing notifications of the sender can be acked
+        for (sender, ids) in &[("bob", vec![5]), ("carol", vec![7])] {
+            match handle(&mut deps, mock_env(*sender, &[]), ack(ids.clone())) {
+                Err(ContractError::NotificationNotFound { .. }) => {}
+                res => panic!("Must return NotificationNotFound, got {:?}", res),
+            }
+        }
+        assert_eq!(
+            notifications(&deps, false).unread_count,
+            MAX_NOTIFICATIONS - 2
+        );
+
+        // Pruning 6 to 8 only removes the unread entry of 7
+        for _ in 0..3 {
+            report(&mut deps);
+        }
+        let res = notifications(&deps, false);
+        assert_eq!(res.notifications[0].notification_id, 9);
+        assert_eq!(res.unread_count, MAX_NOTIFICATIONS);
+    }
+
+    #[test]
+    fn subscriber_overview_shows_the_newest_first() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        create_alert(&mut deps, OWNER, "borrow");
+        subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "75")]).unwrap();
+        subscribe(
+            &mut deps,
+            "bob",
+            "terra.anchor.borrow",
+            vec![value("ltv", "50")],
+        )
+        .unwrap();
+        add_reporter(&mut deps, "reporter");
+        for _ in 0..3 {
+            let msg = report_trigger(ALERT_KEY, "bob", 1);
+            handle(&mut deps, mock_env("reporter", &[]), msg).unwrap();
+        }
+        let msg = HandleMsg::AckNotifications { ids: vec![3] };
+        handle(&mut deps, mock_env("bob", &[]), msg).unwrap();
+
+        let overview = |deps: &MockDeps, subscriber: &str| -> GetSubscriberOverviewResponse {
+            let msg = QueryMsg::GetSubscriberOverview {
+                subscriber_addr: HumanAddr::from(subscriber),
+                limit: Some(1),
+            };
+            from_binary(&query(deps, msg).unwrap()).unwrap()
+        };
+        let res = overview(&deps, "bob");
+        let subscription_ids: Vec<u64> = res
+            .subscriptions
+            .iter()
+            .map(|s| s.subscription_id)
+            .collect();
+        let notification_ids: Vec<u64> = res
+            .notifications
+            .iter()
+            .map(|n| n.notification_id)
+            .collect();
+        assert_eq!(subscription_ids, vec![2]);
+        assert_eq!(res.subscriptions_next_cursor, Some(2));
+        assert_eq!(notification_ids, vec![3]);
+        assert!(res.notifications[0].read);
+        assert_eq!(res.notifications_next_cursor, Some(3));
+        assert_eq!(res.unread_count, 2);
+
+        let res = overview(&deps, "carol");
+        assert!(res.subscriptions.is_empty() && res.notifications.is_empty());
+        assert_eq!(res.subscriptions_next_cursor, None);
+        assert_eq!(res.notifications_next_cursor, None);
+        assert_eq!(res.unread_count, 0);
+    }
+
+    fn set_channel(name: &str) -> HandleMsg {
+        HandleMsg::SetChannel {
+            name: name.to_string(),
+            channel_type: ChannelType::Telegram,
+            ciphertext: Binary::from(b"encrypted handle".to_vec()),
+        }
+    }
+
+    fn channel_names(deps: &MockDeps) -> Vec<String> {
+        let msg = QueryMsg::GetChannels {
+            subscriber_addr: HumanAddr::from("bob"),
+        };
+        let res: GetChannelsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+        res.channels
+            .into_iter()
+            .map(|channel| channel.name)
+            .collect()
+    }
+
+    #[test]
+    fn set_channel_needs_notifier_key_and_is_capped() {
+        let mut deps = setup();
+        match handle(&mut deps, mock_env("bob", &[]), set_channel("main")) {
+            Err(ContractError::NotifierNotSet {}) => {}
+            res => panic!("Must return NotifierNotSet, got {:?}", res),
+        }
+        let msg = HandleMsg::SetNotifierPubkey {
+            pubkey: Binary::from(vec![2; 33]),
+        };
+        match handle(&mut deps, mock_env("bob", &[]), msg.clone()) {
+            Err(ContractError::Unauthorized {}) => {}
+            res => panic!("Must return Unauthorized, got {:?}", res),
+        }
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+
+        for i in 0..MAX_CHANNELS {
+            let res = handle(
+                &mut deps,
+                mock_env("bob", &[]),
+                set_channel(&format!("channel{}", i)),
+            )
+            .unwrap();
+            assert_eq!(res.log[3], log("channel_type", "telegram"));
+        }
+        match handle(&mut deps, mock_env("bob", &[]), set_channel("extra")) {
+            Err(ContractError::InvalidChannel { name, .. }) => assert_eq!(name, "extra"),
+            res => panic!("Must return InvalidChannel, got {:?}", res),
+        }
+        // Existing channels can still be replaced, and other addresses have their own cap
+        handle(&mut deps, mock_env("bob", &[]), set_channel("channel0")).unwrap();
+        handle(&mut deps, mock_env("carol", &[]), set_channel("extra")).unwrap();
+        assert_eq!(channel_names(&deps).len(), MAX_CHANNELS);
+
+        let msg = HandleMsg::RemoveChannel {
+            name: "channel0".to_string(),
+        };
+        handle(&mut deps, mock_env("bob", &[]), msg.clone()).unwrap();
+        match handle(&mut deps, mock_env("bob", &[]), msg) {
+            Err(ContractError::ChannelNotFound { name }) => assert_eq!(name, "channel0"),
+            res => panic!("Must return ChannelNotFound, got {:?}", res),
+        }
+        handle(&mut deps, mock_env("bob", &[]), set_channel("extra")).unwrap();
+        assert!(channel_names(&deps).contains(&"extra".to_string()));
+        assert!(!channel_names(&deps).contains(&"channel0".to_string()));
+    }
+
+    #[test]
+    fn subscription_limits_are_checked_before_normalizing() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        let msg = HandleMsg::UpdateLimits {
+            limits: SubscriptionLimits {
+                max_subscriptions_per_address: 2,
+                max_field_values: 2,
+                max_value_len: 8,
+                deposit: None,
+            },
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+
+        // Unknown and invalid values are only noticed by normalizing, the limits come first
+        let values: Vec<SubscriptionFieldValue> = ["ltv", "note", "x"]
+            .iter()
+            .map(|key| value(key, "1"))
+            .collect();
+        match subscribe(&mut deps, "bob", ALERT_KEY, values) {
+            Err(ContractError::TooManyFieldValues { max }) => assert_eq!(max, 2),
+            res => panic!("Must return TooManyFieldValues, got {:?}", res),
+        }
+        match subscribe(
+            &mut deps,
+            "bob",
+            ALERT_KEY,
+            vec![value("ltv", &"9".repeat(9))],
+        ) {
+            Err(ContractError::FieldValueTooLong { field_key, max_len }) => {
+                assert_eq!(field_key, "ltv");
+                assert_eq!(max_len, 8);
+            }
+            res => panic!("Must return FieldValueTooLong, got {:?}", res),
+        }
+        match subscribe(
+            &mut deps,
+            "bob",
+            ALERT_KEY,
+            vec![value("ltv", "75"), value("ltv", "80")],
+        ) {
+            Err(ContractError::InvalidField { field_key, .. }) => assert_eq!(field_key, "ltv"),
+            res => panic!("Must return InvalidField, got {:?}", res),
+        }
+
+        subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "75")]).unwrap();
+        subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "80")]).unwrap();
+        match subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "85")]) {
+            Err(ContractError::TooManySubscriptions { max }) => assert_eq!(max, 2),
+            res => panic!("Must return TooManySubscriptions, got {:?}", res),
+        }
+        let msg = HandleMsg::UpdateSubscription {
+            subscription_id: 1,
+            field_values: vec![value("ltv", "75"), value("note", &"a".repeat(9))],
+            channels: None,
+        };
+        match handle(&mut deps, mock_env("bob", &[]), msg) {
+            Err(ContractError::FieldValueTooLong { field_key, .. }) => {
+                assert_eq!(field_key, "note")
+            }
+            res => panic!("Must return FieldValueTooLong, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn deposits_are_refunded_on_unsubscribe() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        let msg = HandleMsg::UpdateLimits {
+            limits: SubscriptionLimits {
+                deposit: Some(coin(10, "uusd")),
+                ..SubscriptionLimits::default()
+            },
+        };
+        handle(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+        let msg = HandleMsg::SubscribeAlert {
+            alert_key: ALERT_KEY.to_string(),
+            field_values: vec![value("ltv", "75")],
+            channels: vec![],
+        };
+        match handle(&mut deps, mock_env("bob", &coins(9, "uusd")), msg.clone()) {
+            Err(ContractError::InsufficientFunds { required, .. }) => {
+                assert_eq!(required, "10uusd")
+            }
+            res => panic!("Must return InsufficientFunds, got {:?}", res),
+        }
+        let res = handle(&mut deps, mock_env("bob", &coins(12, "uusd")), msg).unwrap();
+        assert_eq!(payouts(&res), vec![("bob".to_string(), coins(2, "uusd"))]);
+        assert_eq!(bobs_subscription(&deps, 1).deposit, Some(coin(10, "uusd")));
+
+        let unsubscribe = HandleMsg::UnsubscribeAlert { subscription_id: 1 };
+        match handle(&mut deps, mock_env("carol", &[]), unsubscribe.clone()) {
+            Err(ContractError::NotSubscribed { .. }) => {}
+            res => panic!("Must return NotSubscribed, got {:?}", res),
+        }
+        let res = handle(&mut deps, mock_env("bob", &[]), unsubscribe.clone()).unwrap();
+        assert_eq!(payouts(&res), vec![("bob".to_string(), coins(10, "uusd"))]);
+        assert_eq!(res.log[4], log("refund", "10uusd"));
+        // The deposit is only refunded once
+        match handle(&mut deps, mock_env("bob", &[]), unsubscribe) {
+            Err(ContractError::NotSubscribed { .. }) => {}
+            res => panic!("Must return NotSubscribed, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn subscribe_rejects_invalid_values() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+
+        match subscribe(&mut deps, "alice", ALERT_KEY, vec![value("note", "hi")]) {
+            Err(ContractError::MissingField { field_key }) => assert_eq!(field_key, "ltv"),
+            res => panic!("Must return MissingField, got {:?}", res),
+        }
+        match subscribe(&mut deps, "alice", ALERT_KEY, vec![value("ltv", "101")]) {
+            Err(ContractError::InvalidField { field_key, .. }) => assert_eq!(field_key, "ltv"),
+            res => panic!("Must return InvalidField, got {:?}", res),
+        }
+        match subscribe(&mut deps, "alice", "terra.anchor.unknown", vec![]) {
+            Err(ContractError::AlertNotFound { .. }) => {}
+            res => panic!("Must return AlertNotFound, got {:?}", res),
+        }
+        assert!(subscription_ids(&deps, "alice", OrderBy::Asc).is_empty());
+    }
+
+    #[test]
+    fn unsubscribe_alert() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        subscribe(&mut deps, "alice", ALERT_KEY, vec![value("ltv", "80")]).unwrap();
+
+        // Subscriptions can only be removed by their subscriber
+        let msg = HandleMsg::UnsubscribeAlert { subscription_id: 1 };
+        match handle(&mut deps, mock_env("bob", &[]), msg.clone()) {
+            Err(ContractError::NotSubscribed { subscription_id }) => assert_eq!(subscription_id, 1),
+            res => panic!("Must return NotSubscribed, got {:?}", res),
+        }
+
+        let res = handle(&mut deps, mock_env("alice", &[]), msg.clone()).unwrap();
+        assert_eq!(res.log[0], log("action", "unsubscribe_alert"));
+        assert!(subscription_ids(&deps, "alice", OrderBy::Asc).is_empty());
+        assert!(subscriber_ids(&deps, ALERT_KEY, OrderBy::Asc).is_empty());
+
+        match handle(&mut deps, mock_env("alice", &[]), msg) {
+            Err(ContractError::NotSubscribed { .. }) => {}
+            res => panic!("Must return NotSubscribed, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn paginates_alerts_in_both_orders() {
+        let mut deps = setup();
+        for method in &["a", "b", "c", "d", "e"] {
+            create_alert(&mut deps, OWNER, method);
+        }
+
+        for (order_by, expected) in &[
+            (OrderBy::Asc, ["a", "b", "c", "d", "e"]),
+            (OrderBy::Desc, ["e", "d", "c", "b", "a"]),
+        ] {
+            let mut methods: Vec<String> = vec![];
+            let mut start_after: Option<String> = None;
+            loop {
+                let msg = QueryMsg::GetAlerts {
+                    start_after,
+                    limit: Some(2),
+                    order_by: Some(order_by.clone()),
+                };
+                let res: GetAlertsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+                assert!(res.alerts.len() <= 2);
+                methods.extend(res.alerts.into_iter().map(|alert| alert.method));
+                match res.next_cursor {
+                    Some(cursor) => start_after = Some(cursor),
+                    None => break,
+                }
+            }
+            assert_eq!(methods, expected);
+        }
+    }
+
+    #[test]
+    fn paginates_subscriptions_in_both_orders() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        for ltv in &["10", "20", "30", "40", "50"] {
+            subscribe(&mut deps, "alice", ALERT_KEY, vec![value("ltv", ltv)]).unwrap();
+        }
+        subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "60")]).unwrap();
+
+        assert_eq!(
+            subscription_ids(&deps, "alice", OrderBy::Asc),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            subscription_ids(&deps, "alice", OrderBy::Desc),
+            vec![5, 4, 3, 2, 1]
+        );
+        assert_eq!(
+            subscriber_ids(&deps, ALERT_KEY, OrderBy::Asc),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            subscriber_ids(&deps, ALERT_KEY, OrderBy::Desc),
+            vec![6, 5, 4, 3, 2, 1]
+        );
+    }
+
+    #[test]
+    fn storage_layout() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "liquidation");
+        subscribe(&mut deps, "alice", ALERT_KEY, vec![value("ltv", "80")]).unwrap();
+        let alice: CanonicalAddr = deps
+            .api
+            .canonical_address(&HumanAddr::from("alice"))
+            .unwrap();
+        let id: [u8; 8] = 1u64.to_be_bytes();
+        let key = |namespaces: &[&[u8]], key: &[u8]| -> Vec<u8> {
+            [to_length_prefixed_nested(namespaces), key.to_vec()].concat()
+        };
+
+        let alert: Alert = from_slice(
+            &deps
+                .storage
+                .get(&[to_length_prefixed(b"alert"), ALERT_KEY.as_bytes().to_vec()].concat())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(alert.alert_key, ALERT_KEY);
+        assert!(deps
+            .storage
+            .get(&key(&[b"blockchain_alert", b"terra"], ALERT_KEY.as_bytes()))
+            .is_some());
+        assert!(deps
+            .storage
+            .get(&key(
+                &[b"protocol_alert", b"terra", b"anchor"],
+                ALERT_KEY.as_bytes()
+            ))
+            .is_some());
+        let count: u64 = from_slice(
+            &deps
+                .storage
+                .get(&key(&[b"blockchain_count"], b"terra"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let subscription: Subscription = from_slice(
+            &deps
+                .storage
+                .get(&key(&[b"subscription", alice.as_slice()], &id))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(subscription.subscription_id, 1);
+        let subscriber: CanonicalAddr = from_slice(
+            &deps
+                .storage
+                .get(&key(&[b"alert_subscription", ALERT_KEY.as_bytes()], &id))
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(subscriber, alice);
+        assert!(deps
+            .storage
+            .get(&key(
+                &[b"address_alert", alice.as_slice(), ALERT_KEY.as_bytes()],
+                &id
+            ))
+            .is_some());
+
+        // Removing the last subscription leaves neither records nor counters behind
+        let msg = HandleMsg::UnsubscribeAlert { subscription_id: 1 };
+        handle(&mut deps, mock_env("alice", &[]), msg).unwrap();
+        assert!(deps
+            .storage
+            .get(&key(&[b"subscription", alice.as_slice()], &id))
+            .is_none());
+        assert!(deps
+            .storage
+            .get(&key(&[b"alert_subscription", ALERT_KEY.as_bytes()], &id))
+            .is_none());
+        assert!(deps
+            .storage
+            .get(&key(&[b"address_subscription_count"], alice.as_slice()))
+            .is_none());
+    }
+
+    #[test]
+    fn migrates_v1_storage_in_batches() {
+        let mut deps = mock_dependencies(20, &[]);
+        let canonical = |deps: &MockDeps, addr: &str| -> CanonicalAddr {
+            deps.api.canonical_address(&HumanAddr::from(addr)).unwrap()
+        };
+        let (owner, alice, bob) = (
+            canonical(&deps, OWNER),
+            canonical(&deps, "alice"),
+            canonical(&deps, "bob"),
+        );
+        let key = |namespaces: &[&[u8]], key: &[u8]| -> Vec<u8> {
+            [to_length_prefixed_nested(namespaces), key.to_vec()].concat()
+        };
+
+        // Version 1 stored neither a version nor ids, subscriptions were keyed by alert
+        let config = format!(
+            r#"{{"owner":{}}}"#,
+            String::from_utf8(to_vec(&owner).unwrap()).unwrap()
+        );
+        deps.storage
+            .set(&to_length_prefixed(b"config"), config.as_bytes());
+        for alert_key in &["terra.anchor.a", "terra.anchor.b", "terra.mirror.c"] {
+            let parts: Vec<&str> = alert_key.split('.').collect();
+            let alert = format!(
+                r#"{{"alert_key":"{}","blockchain":"{}","protocol":"{}","method":"{}","name":"n","description":"d","fields":[{{"field_key":"ltv","name":"LTV","description":"","validation_regex":""}}]}}"#,
+                alert_key, parts[0], parts[1], parts[2]
+            );
+            deps.storage
+                .set(&key(&[b"alert"], alert_key.as_bytes()), alert.as_bytes());
+        }
+        for (subscriber, alert_key) in &[
+            (&alice, "terra.anchor.a"),
+            (&alice, "terra.anchor.b"),
+            (&bob, "terra.anchor.a"),
+        ] {
+            let subscription = format!(
+                r#"{{"alert_key":"{}","field_values":[{{"field_key":"ltv","value":"75"}}]}}"#,
+                alert_key
+            );
+            deps.storage.set(
+                &key(
+                    &[b"subscription", subscriber.as_slice()],
+                    alert_key.as_bytes(),
+                ),
+                subscription.as_bytes(),
+            );
+            deps.storage.set(
+                &key(
+                    &[b"alert_subscriber", alert_key.as_bytes()],
+                    subscriber.as_slice(),
+                ),
+                b"true",
+            );
+        }
+
+        let mut calls = 0;
+        loop {
+            calls += 1;
+            let msg = MigrateMsg { limit: Some(2) };
+            let res = migrate(&mut deps, mock_env(OWNER, &[]), msg).unwrap();
+            assert_eq!(res.log[1], log("from_version", 1));
+            if res.log[3] == log("complete", true) {
+                break;
+            }
+            assert_eq!(read_contract_version(&deps.storage).unwrap(), 1);
+            match handle(
+                &mut deps,
+                mock_env(OWNER, &[]),
+                HandleMsg::DeprecateAlert {
+                    alert_key: "terra.anchor.a".to_string(),
+                },
+            ) {
+                Err(ContractError::MigrationInProgress {}) => {}
+                res => panic!("Must return MigrationInProgress, got {:?}", res),
+            }
+            match query(
+                &deps,
+                QueryMsg::GetStats {
+                    subscriber_addr: None,
+                },
+            ) {
+                Err(ContractError::MigrationInProgress {}) => {}
+                res => panic!("Must return MigrationInProgress, got {:?}", res),
+            }
+        }
+        // 3 legacy index entries, 3 alerts and 3 subscriptions
+        assert_eq!(calls, 5);
+        assert_eq!(
+            read_contract_version(&deps.storage).unwrap(),
+            CONTRACT_VERSION
+        );
+
+        // Subscriptions got ids in key order and moved under them
+        let ids: Vec<u64> = [
+            key(&[b"subscription", alice.as_slice()], &1u64.to_be_bytes()),
+            key(&[b"subscription", alice.as_slice()], &2u64.to_be_bytes()),
+            key(&[b"subscription", bob.as_slice()], &3u64.to_be_bytes()),
+        ]
+        .iter()
+        .map(|k| {
+            let subscription: Subscription = from_slice(&deps.storage.get(k).unwrap()).unwrap();
+            subscription.subscription_id
+        })
+        .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert!(deps
+            .storage
+            .get(&key(
+                &[b"subscription", alice.as_slice()],
+                b"terra.anchor.a"
+            ))
+            .is_none());
+        let legacy_index = to_length_prefixed(b"alert_subscriber");
+        assert!(deps
+            .storage
+            .range(Some(&legacy_index), None, Order::Ascending)
+            .all(|(k, _)| !k.starts_with(&legacy_index)));
+
+        // Indexes and counters are rebuilt
+        assert_eq!(
+            subscriber_ids(&deps, "terra.anchor.a", OrderBy::Asc),
+            vec![1, 3]
+        );
+        assert_eq!(
+            count_subscriptions_for_alert(&deps.storage, &"terra.anchor.a".to_string()).unwrap(),
+            (2, 2)
+        );
+        assert_eq!(
+            count_subscriptions_for_address(&deps.storage, &alice).unwrap(),
+            2
+        );
+        let res: GetStatsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::GetStats {
+                    subscriber_addr: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!((res.total_alerts, res.total_subscriptions), (3, 3));
+        let res: ListProtocolsResponse = from_binary(
+            &query(
+                &deps,
+                QueryMsg::ListProtocols {
+                    blockchain: "terra".to_string(),
+                    start_after: None,
+                    limit: None,
+                    order_by: Some(OrderBy::Asc),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let counts: Vec<u64> = res.protocols.iter().map(|p| p.alert_count).collect();
+        assert_eq!(counts, vec![2, 1]);
+
+        // The contract works as usual, migrating again changes nothing
+        let res = subscribe(&mut deps, "bob", "terra.mirror.c", vec![value("ltv", "75")]).unwrap();
+        assert_eq!(res.log[3], log("subscription_id", 4));
+        let res = migrate(
+            &mut deps,
+            mock_env(OWNER, &[]),
+            MigrateMsg { limit: Some(1) },
+        )
+        .unwrap();
+        assert_eq!(
+            res.log,
+            vec![
+                log("action", "migrate"),
+                log("from_version", CONTRACT_VERSION),
+                log("to_version", CONTRACT_VERSION),
+                log("complete", true),
+            ]
+        );
+        assert_eq!(
+            subscriber_ids(&deps, "terra.mirror.c", OrderBy::Asc),
+            vec![4]
+        );
+
+        // Versions without a migration are refused
+        store_contract_version(&mut deps.storage, CONTRACT_VERSION + 1).unwrap();
+        match migrate(&mut deps, mock_env(OWNER, &[]), MigrateMsg { limit: None }) {
+            Err(ContractError::UnsupportedVersion { from, .. }) => {
+                assert_eq!(from, CONTRACT_VERSION + 1)
+            }
+            res => panic!("Must return UnsupportedVersion, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn legacy_keys_are_found_in_any_case_after_migrating() {
+        let mut deps = setup();
+        create_alert(&mut deps, OWNER, "borrow");
+
+        // Version 1 kept keys as given and indexed them in that case
+        let mut legacy: Alert =
+            read_alert(&deps.storage, &"terra.anchor.borrow".to_string()).unwrap();
+        legacy.alert_key = "Terra.Anchor.Liquidation".to_string();
+        legacy.blockchain = "Terra".to_string();
+        legacy.protocol = "Anchor".to_string();
+        legacy.method = "Liquidation".to_string();
+        deps.storage.set(
+            &[
+                to_length_prefixed(b"alert"),
+                legacy.alert_key.as_bytes().to_vec(),
+            ]
+            .concat(),
+            &to_vec(&legacy).unwrap(),
+        );
+        deps.storage
+            .remove(&to_length_prefixed(b"contract_version"));
+
+        let res = migrate(&mut deps, mock_env(OWNER, &[]), MigrateMsg { limit: None }).unwrap();
+        assert_eq!(res.log[1], log("from_version", 1));
+        assert_eq!(res.log[3], log("complete", true));
+
+        let msg = QueryMsg::GetAlert {
+            alert_key: ALERT_KEY.to_string(),
+        };
+        let res: GetAlertResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.alert.alert_key, "Terra.Anchor.Liquidation");
+        let msg = QueryMsg::GetAlertsBy {
+            blockchain: "TERRA".to_string(),
+            protocol: Some("anchor".to_string()),
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        let res: GetAlertsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.alerts.len(), 2);
+        let msg = QueryMsg::ListProtocols {
+            blockchain: "Terra".to_string(),
+            start_after: None,
+            limit: None,
+            order_by: None,
+        };
+        let res: ListProtocolsResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.protocols.len(), 1);
+        assert_eq!(
+            (
+                res.protocols[0].protocol.as_str(),
+                res.protocols[0].alert_count
+            ),
+            ("anchor", 2)
+        );
+
+        // Subscriptions use the stored key, whatever case they were made with
+        let res = subscribe(&mut deps, "bob", ALERT_KEY, vec![value("ltv", "75")]).unwrap();
+        assert_eq!(res.log[1], log("alert_key", "Terra.Anchor.Liquidation"));
+        assert_eq!(is_subscribed(&deps, 0), vec![1]);
+
+        // The canonical key would shadow the legacy alert
+        let msg = create_alert_msg(alert_params("liquidation"));
+        match handle(&mut deps, mock_env(OWNER, &[]), msg) {
+            Err(ContractError::AlertAlreadyExists { alert_key }) => {
+                assert_eq!(alert_key, "Terra.Anchor.Liquidation")
+            }
+            res => panic!("Must return AlertAlreadyExists, got {:?}", res),
+        }
+    }
+
+    // Small xorshift generator, so random handle sequences are reproducible from their seed
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    // Runs random sequences of handle messages and compares the state against a simple model
+    #[test]
+    fn random_handle_sequences_keep_state_consistent() {
+        let methods = ["a", "b", "c"];
+        let subscribers = ["alice", "bob", "carol"];
+
+        for seed in 1..=40u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let mut deps = setup();
+            // subscription id -> (subscriber, alert key), as the contract should have it
+            let mut model: BTreeMap<u64, (String, String)> = BTreeMap::new();
+            let mut alerts: BTreeSet<String> = BTreeSet::new();
+            let mut next_id: u64 = 1;
+
+            for _ in 0..40 {
+                let alert_key = format!("terra.anchor.{}", methods[rng.below(methods.len())]);
+                let subscriber = subscribers[rng.below(subscribers.len())];
+                match rng.below(5) {
+                    0 => {
+                        let exists = alerts.contains(&alert_key);
+                        let msg =
+                            create_alert_msg(alert_params(&alert_key["terra.anchor.".len()..]));
+                        let res = handle(&mut deps, mock_env(OWNER, &[]), msg);
+                        assert_eq!(res.is_ok(), !exists, "seed {}", seed);
+                        alerts.insert(alert_key);
+                    }
+                    1 | 2 => {
+                        let res =
+                            subscribe(&mut deps, subscriber, &alert_key, vec![value("ltv", "50")]);
+                        assert_eq!(res.is_ok(), alerts.contains(&alert_key), "seed {}", seed);
+                        if res.is_ok() {
+                            model.insert(next_id, (subscriber.to_string(), alert_key));
+                            next_id += 1;
+                        }
+                    }
+                    3 => {
+                        let subscription_id = 1 + rng.below(next_id as usize) as u64;
+                        let msg = HandleMsg::UnsubscribeAlert { subscription_id };
+                        let res = handle(&mut deps, mock_env(subscriber, &[]), msg);
+                        let owned = model
+                            .get(&subscription_id)
+                            .map(|(owner, _)| owner == subscriber)
+                            .unwrap_or(false);
+                        assert_eq!(res.is_ok(), owned, "seed {}", seed);
+                        if owned {
+                            model.remove(&subscription_id);
+                        }
+                    }
+                    _ => {
+                        let msg = HandleMsg::DeleteAlert {
+                            alert_key: alert_key.clone(),
+                        };
+                        let res = handle(&mut deps, mock_env(OWNER, &[]), msg);
+                        assert_eq!(res.is_ok(), alerts.remove(&alert_key), "seed {}", seed);
+                        model.retain(|_, (_, key)| *key != alert_key);
+                    }
+                }
+                check_invariants(&deps, &model, &alerts, seed);
+            }
+        }
+    }
+
+    fn check_invariants(
+        deps: &MockDeps,
+        model: &BTreeMap<u64, (String, String)>,
+        alerts: &BTreeSet<String>,
+        seed: u64,
+    ) {
+        let res: GetStatsResponse = from_binary(
+            &query(
+                deps,
+                QueryMsg::GetStats {
+                    subscriber_addr: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.total_alerts, alerts.len() as u64, "seed {}", seed);
+        assert_eq!(res.total_subscriptions, model.len() as u64, "seed {}", seed);
+
+        for subscriber in &["alice", "bob", "carol"] {
+            let expected: Vec<u64> = model
+                .iter()
+                .filter(|(_, (owner, _))| owner == subscriber)
+                .map(|(id, _)| *id)
+                .collect();
+            let asc: Vec<u64> = subscription_ids(deps, subscriber, OrderBy::Asc);
+            let mut desc: Vec<u64> = subscription_ids(deps, subscriber, OrderBy::Desc);
+            desc.reverse();
+            assert_eq!(asc, expected, "seed {}", seed);
+            assert_eq!(desc, expected, "seed {}", seed);
+        }
+
+        for alert_key in alerts {
+            let expected: Vec<u64> = model
+                .iter()
+                .filter(|(_, (_, key))| key == alert_key)
+                .map(|(id, _)| *id)
+                .collect();
+            assert_eq!(
+                subscriber_ids(deps, alert_key, OrderBy::Asc),
+                expected,
+                "seed {}",
+                seed
+            );
+
+            let msg = QueryMsg::GetAlertStats {
+                alert_key: alert_key.clone(),
+            };
+            let res: GetAlertStatsResponse = from_binary(&query(deps, msg).unwrap()).unwrap();
+            let distinct: BTreeSet<&String> = model
+                .values()
+                .filter(|(_, key)| key == alert_key)
+                .map(|(owner, _)| owner)
+                .collect();
+            assert_eq!(
+                res.subscription_count,
+                expected.len() as u64,
+                "seed {}",
+                seed
+            );
+            assert_eq!(res.subscriber_count, distinct.len() as u64, "seed {}", seed);
+        }
+    }
 }
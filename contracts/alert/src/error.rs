@@ -0,0 +1,180 @@
+use cosmwasm_std::StdError;
+use snafu::Snafu;
+
+/**
+ * Errors returned by the alert contract.
+ *
+ * Every message starts with a snake_case error code followed by a colon, e.g.
+ * `alert_not_found: Alert terra.anchor.liquidation does not exist`. The codes are
+ * stable, so clients should branch on the code and only show the rest to users.
+ */
+#[derive(Snafu, Debug)]
+#[snafu(visibility = "pub")]
+pub enum ContractError {
+    #[snafu(display("std_error: {}", source))]
+    Std { source: StdError },
+
+    #[snafu(display("unauthorized: Sender is not allowed to perform this action"))]
+    Unauthorized {},
+
+    #[snafu(display("creator_already_exists: {} is already a creator", creator))]
+    CreatorAlreadyExists { creator: String },
+
+    #[snafu(display("creator_not_found: {} is not a creator", creator))]
+    CreatorNotFound { creator: String },
+
+    #[snafu(display("alert_not_found: Alert {} does not exist", alert_key))]
+    AlertNotFound { alert_key: String },
+
+    #[snafu(display("alert_already_exists: Alert {} already exists", alert_key))]
+    AlertAlreadyExists { alert_key: String },
+
+    #[snafu(display("invalid_alert_key: Invalid alert key {}: {}", alert_key, reason))]
+    InvalidAlertKey { alert_key: String, reason: String },
+
+    #[snafu(display("alert_deprecated: Alert {} is deprecated", alert_key))]
+    AlertDeprecated { alert_key: String },
+
+    #[snafu(display("template_not_found: Template {} does not exist", template_id))]
+    TemplateNotFound { template_id: u64 },
+
+    #[snafu(display(
+        "template_in_use: Template {} is used by {} alerts",
+        template_id,
+        alert_count
+    ))]
+    TemplateInUse { template_id: u64, alert_count: u64 },
+
+    #[snafu(display("no_template: Alert {} was not created from a template", alert_key))]
+    NoTemplate { alert_key: String },
+
+    #[snafu(display("not_subscribed: Address has no subscription {}", subscription_id))]
+    NotSubscribed { subscription_id: u64 },
+
+    #[snafu(display("subscription_expired: Subscription {} expired", subscription_id))]
+    SubscriptionExpired { subscription_id: u64 },
+
+    #[snafu(display("reporter_already_exists: {} is already a reporter", reporter))]
+    ReporterAlreadyExists { reporter: String },
+
+    #[snafu(display("reporter_not_found: {} is not a reporter", reporter))]
+    ReporterNotFound { reporter: String },
+
+    #[snafu(display(
+        "notification_not_found: Sender has no notification {}",
+        notification_id
+    ))]
+    NotificationNotFound { notification_id: u64 },
+
+    #[snafu(display("channel_not_found: No channel named {}", name))]
+    ChannelNotFound { name: String },
+
+    #[snafu(display("invalid_channel: Invalid channel {}: {}", name, reason))]
+    InvalidChannel { name: String, reason: String },
+
+    #[snafu(display("notifier_not_set: The notifier public key has not been registered"))]
+    NotifierNotSet {},
+
+    #[snafu(display(
+        "too_many_subscriptions: Address already has the maximum of {} subscriptions",
+        max
+    ))]
+    TooManySubscriptions { max: u64 },
+
+    #[snafu(display("too_many_field_values: At most {} field values are allowed", max))]
+    TooManyFieldValues { max: u64 },
+
+    #[snafu(display(
+        "field_value_too_long: Value of field {} is longer than {} bytes",
+        field_key,
+        max_len
+    ))]
+    FieldValueTooLong { field_key: String, max_len: u64 },
+
+    #[snafu(display("invalid_limits: Invalid subscription limits: {}", reason))]
+    InvalidLimits { reason: String },
+
+    #[snafu(display("invalid_key_scheme: Invalid key scheme: {}", reason))]
+    InvalidKeyScheme { reason: String },
+
+    #[snafu(display("payload_too_long: Payload is longer than {} bytes", max_len))]
+    PayloadTooLong { max_len: usize },
+
+    #[snafu(display("missing_field: Missing value for field {}", field_key))]
+    MissingField { field_key: String },
+
+    #[snafu(display("unknown_field: Alert has no field {}", field_key))]
+    UnknownField { field_key: String },
+
+    #[snafu(display("invalid_field: Invalid field {}: {}", field_key, reason))]
+    InvalidField { field_key: String, reason: String },
+
+    #[snafu(display("invalid_tag: Invalid tag {}: {}", tag, reason))]
+    InvalidTag { tag: String, reason: String },
+
+    #[snafu(display("invalid_price: Invalid price: {}", reason))]
+    InvalidPrice { reason: String },
+
+    #[snafu(display("invalid_treasury_fee: Treasury fee of {}% is above 100%", fee_percent))]
+    InvalidTreasuryFee { fee_percent: u64 },
+
+    #[snafu(display(
+        "insufficient_funds: Subscription requires {} but {} was sent",
+        required,
+        sent
+    ))]
+    InsufficientFunds { required: String, sent: String },
+
+    #[snafu(display(
+        "amount_overflow: Required amount of {} does not fit in 128 bits",
+        denom
+    ))]
+    AmountOverflow { denom: String },
+
+    #[snafu(display(
+        "unsupported_version: Cannot migrate from contract version {} to {}",
+        from,
+        to
+    ))]
+    UnsupportedVersion { from: u64, to: u64 },
+
+    #[snafu(display("migration_in_progress: Send MigrateMsg again to finish the migration"))]
+    MigrationInProgress {},
+
+    #[snafu(display("invalid_regex: Invalid regex {:?}: {}", pattern, reason))]
+    InvalidRegex { pattern: String, reason: String },
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_starts_with_the_error_code() {
+        let err = ContractError::NotSubscribed { subscription_id: 7 };
+        assert_eq!(
+            err.to_string(),
+            "not_subscribed: Address has no subscription 7"
+        );
+        let err = ContractError::AlertDeprecated {
+            alert_key: "terra.anchor.liquidation".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "alert_deprecated: Alert terra.anchor.liquidation is deprecated"
+        );
+        let err = ContractError::MissingField {
+            field_key: "ltv".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "missing_field: Missing value for field ltv"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Log attributes emitted by the alert contract.
+//!
+//! Every successful handle message, and the migration, returns an `action` attribute first,
+//! followed by the attributes listed for it below. Attribute names and value formats are stable, new
+//! attributes may be appended but existing ones are never renamed or removed, so an
+//! indexer can tail the tx results instead of replaying state.
+//!
+//! | action                | attributes                                                       |
+//! |-----------------------|------------------------------------------------------------------|
+//! | `create_alert`        | `alert_key`, `creator`, `fields_hash`, `price`, `template_id`, `severity`, `category`, `tags` |
+//! | `update_alert`        | `alert_key`, `sender`, `fields_hash`, `severity`, `category`, `tags` |
+//! | `deprecate_alert`     | `alert_key`, `sender`                                            |
+//! | `delete_alert`        | `alert_key`, `sender`, `remaining_subscriptions`                 |
+//! | `apply_template`      | `alert_key`, `sender`, `template_id`, `version`, `fields_hash`   |
+//! | `set_alert_price`     | `alert_key`, `sender`, `price`                                   |
+//! | `create_template`     | `template_id`, `creator`, `fields_hash`                          |
+//! | `update_template`     | `template_id`, `sender`, `version`, `fields_hash`, `alert_count` |
+//! | `delete_template`     | `template_id`, `sender`                                          |
+//! | `subscribe_alert`     | `alert_key`, `subscriber`, `subscription_id`, `field_values_hash`, `expires_at`, `deposit` |
+//! | `update_subscription` | `alert_key`, `subscriber`, `subscription_id`, `field_values_hash` |
+//! | `renew_subscription`  | `alert_key`, `subscriber`, `subscription_id`, `expires_at`       |
+//! | `unsubscribe_alert`   | `alert_key`, `subscriber`, `subscription_id`, `refund`           |
+//! | `report_trigger`      | `alert_key`, `subscriber`, `subscription_id`, `notification_id`, `reporter`, `block_ref` |
+//! | `ack_notifications`   | `subscriber`, `notification_ids`                                 |
+//! | `set_channel`         | `owner`, `name`, `channel_type`                                  |
+//! | `remove_channel`      | `owner`, `name`                                                  |
+//! | `add_creator`         | `creator`                                                        |
+//! | `remove_creator`      | `creator`                                                        |
+//! | `add_reporter`        | `reporter`                                                       |
+//! | `remove_reporter`     | `reporter`                                                       |
+//! | `propose_owner`       | `owner`, `pending_owner`                                         |
+//! | `transfer_ownership`  | `previous_owner`, `new_owner`                                    |
+//! | `update_treasury`     | `treasury`, `treasury_fee_percent`                               |
+//! | `set_notifier_pubkey` | `pubkey`                                                         |
+//! | `update_limits`       | `max_subscriptions_per_address`, `max_field_values`, `max_value_len`, `deposit` |
+//! | `migrate`             | `from_version`, `to_version`, `complete`                         |
+//!
+//! Value formats:
+//! - `alert_key` is `[<creator>/]<blockchain>.<protocol>.<method>`, see `keys`
+//! - addresses are human addresses, `subscription_id` and `notification_id` are decimal numbers
+//! - `price` is `<amount><denom>/<billing period in seconds>`, e.g. `1000uusd/86400`, or `free`
+//! - `expires_at` is a block time in seconds, or `never`
+//! - `transfer_ownership` is emitted once the proposed owner sent AcceptOwnership
+//! - `treasury` is an address, or `none`
+//! - `template_id` and `version` are decimal numbers, `template_id` of `create_alert` can be `none`
+//! - `severity` is `info`, `warning` or `critical`, `category` is a category or `none`
+//! - `tags` is a comma separated list of the tags, or `none` if there are none
+//! - `alert_count` is the number of alerts created from the template, see GetAlertsByTemplate
+//! - `deposit` and `refund` are `<amount><denom>`, e.g. `1000000uluna`, or `none`
+//! - `notification_ids` is a comma separated list of the acknowledged ids
+//! - `complete` is `false` while a migration needs more MigrateMsg, `true` once it finished
+//! - `remaining_subscriptions` is the number of subscriptions a later DeleteAlert still removes
+//! - `channel_type` is `email`, `telegram`, `discord` or `webhook`, `pubkey` is base64
+//! - `fields_hash` and `field_values_hash` are 16 hex digits identifying the alert fields and
+//!   the normalized subscription values, see `hash_fields` and `hash_field_values`. They only
+//!   tell whether something changed, they are not cryptographic.
+
+use crate::models::{
+    AlertField, AlertPrice, AlertTemplateRef, ChannelType, SubscriptionFieldValue,
+};
+use cosmwasm_std::{to_vec, Coin, StdResult};
+
+pub const NONE: &str = "none";
+pub const NEVER: &str = "never";
+pub const FREE: &str = "free";
+
+pub fn format_price(price: &Option<AlertPrice>) -> String {
+    match price {
+        Some(price) => format!("{}{}/{}", price.amount, price.denom, price.billing_period),
+        None => FREE.to_string(),
+    }
+}
+
+pub fn format_expires_at(expires_at: Option<u64>) -> String {
+    match expires_at {
+        Some(expires_at) => expires_at.to_string(),
+        None => NEVER.to_string(),
+    }
+}
+
+pub fn format_coin(coin: &Option<Coin>) -> String {
+    match coin {
+        Some(coin) => format!("{}{}", coin.amount, coin.denom),
+        None => NONE.to_string(),
+    }
+}
+
+pub fn format_template_id(template: &Option<AlertTemplateRef>) -> String {
+    match template {
+        Some(template) => template.template_id.to_string(),
+        None => NONE.to_string(),
+    }
+}
+
+pub fn format_category(category: &Option<String>) -> String {
+    match category {
+        Some(category) => category.clone(),
+        None => NONE.to_string(),
+    }
+}
+
+pub fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        NONE.to_string()
+    } else {
+        tags.join(",")
+    }
+}
+
+pub fn format_channel_type(channel_type: &ChannelType) -> &'static str {
+    match channel_type {
+        ChannelType::Email => "email",
+        ChannelType::Telegram => "telegram",
+        ChannelType::Discord => "discord",
+        ChannelType::Webhook => "webhook",
+    }
+}
+
+// Hash of the JSON encoded alert fields
+pub fn hash_fields(fields: &[AlertField]) -> StdResult<String> {
+    Ok(fnv1a_hex(&to_vec(fields)?))
+}
+
+// Hash of the JSON encoded normalized subscription values, in the order of the alert fields
+pub fn hash_field_values(field_values: &[SubscriptionFieldValue]) -> StdResult<String> {
+    Ok(fnv1a_hex(&to_vec(field_values)?))
+}
+
+// 64 bit FNV-1a, small enough to not need an extra dependency
+fn fnv1a_hex(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a_hex(b""), "cbf29ce484222325");
+        assert_eq!(fnv1a_hex(b"a"), "af63dc4c8601ec8c");
+        assert_eq!(fnv1a_hex(b"foobar"), "85944171f73967e8");
+    }
+
+    #[test]
+    fn field_values_hash_depends_on_values() {
+        let values = |value: &str| {
+            vec![SubscriptionFieldValue {
+                field_key: "threshold".to_string(),
+                value: value.to_string(),
+            }]
+        };
+        assert_eq!(
+            hash_field_values(&values("10")).unwrap(),
+            hash_field_values(&values("10")).unwrap()
+        );
+        assert_ne!(
+            hash_field_values(&values("10")).unwrap(),
+            hash_field_values(&values("11")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::error::ContractError;
+use crate::models::KeyScheme;
+use std::fmt;
+
+pub const SEPARATOR: char = '.';
+pub const NAMESPACE_SEPARATOR: char = '/';
+pub const ESCAPED_SEPARATOR: &str = "%2e";
+pub const MAX_COMPONENT_LEN: usize = 64;
+
+/**
+ * Alert keys.
+ *
+ * An alert key is `[<namespace>/]<blockchain>.<protocol>.<method>`, e.g.
+ * `terra.anchor.liquidation`. Alerts created by the owner have no namespace, alerts created by
+ * anyone else are prefixed with the creator address, so creators cannot take each other's keys.
+ * Canonical keys parse back into their components with `AlertKey::parse`.
+ *
+ * Components are canonicalized before the key is built:
+ * - ASCII letters are folded to lower case, so `Terra` and `terra` are the same blockchain
+ * - only `a-z`, `0-9`, `_`, `-` and `.` are allowed, with at most 64 characters
+ * - a `.` inside a component is escaped as `%2e`, `%` never appears otherwise
+ *
+ * The namespace follows the same rules but cannot contain `.`.
+ *
+ * Alerts created before these rules keep their keys. Lookups fold the key they are given with
+ * `fold`, and alerts are also indexed under their folded key, so `Terra.Anchor.liquidation` finds
+ * the alert whichever of the two spellings it was stored under. The same index keeps a new alert
+ * from taking the folded key of an old one.
+ *
+ * `KeyScheme` configures the derivation: whether creator alerts are namespaced, and a lower
+ * limit on the component length. It is set at init and can be read with GetKeyScheme, but not
+ * changed afterwards, since keys are stored in subscriptions, notifications and indexes. Case
+ * folding, the allowed characters and the escaping are the same under every scheme, folded
+ * lookups and `parse` depend on them.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertKey {
+    pub namespace: Option<String>,
+    pub blockchain: String,
+    pub protocol: String,
+    pub method: String,
+}
+
+impl AlertKey {
+    // Canonicalizes the components, fails if one of them cannot be part of a key
+    pub fn new(
+        scheme: &KeyScheme,
+        namespace: Option<&str>,
+        blockchain: &str,
+        protocol: &str,
+        method: &str,
+    ) -> Result<Self, ContractError> {
+        let max_len: usize = (scheme.max_component_len as usize).min(MAX_COMPONENT_LEN);
+        let canonicalize = || -> Result<AlertKey, String> {
+            Ok(AlertKey {
+                namespace: match namespace {
+                    Some(namespace) => Some(canonical_namespace(namespace)?),
+                    None => None,
+                },
+                blockchain: canonical_component("blockchain", blockchain, max_len)?,
+                protocol: canonical_component("protocol", protocol, max_len)?,
+                method: canonical_component("method", method, max_len)?,
+            })
+        };
+        canonicalize().map_err(|reason| ContractError::InvalidAlertKey {
+            alert_key: match namespace {
+                Some(namespace) => format!("{}/{}.{}.{}", namespace, blockchain, protocol, method),
+                None => format!("{}.{}.{}", blockchain, protocol, method),
+            },
+            reason,
+        })
+    }
+
+    // Only accepts keys in canonical form, so parsing and formatting round trip
+    pub fn parse(scheme: &KeyScheme, alert_key: &str) -> Result<Self, ContractError> {
+        let invalid = |reason: &str| ContractError::InvalidAlertKey {
+            alert_key: alert_key.to_string(),
+            reason: reason.to_string(),
+        };
+        let (namespace, path) = match alert_key.find(NAMESPACE_SEPARATOR) {
+            Some(pos) => (Some(&alert_key[..pos]), &alert_key[pos + 1..]),
+            None => (None, alert_key),
+        };
+        let components: Vec<String> = path
+            .split(SEPARATOR)
+            .map(|component| component.replace(ESCAPED_SEPARATOR, "."))
+            .collect();
+        if components.len() != 3 {
+            return Err(invalid("expected blockchain.protocol.method"));
+        }
+        let key = AlertKey::new(
+            scheme,
+            namespace,
+            &components[0],
+            &components[1],
+            &components[2],
+        )
+        .map_err(|_| invalid("not a canonical key"))?;
+        if key.to_string() != alert_key {
+            return Err(invalid("not a canonical key"));
+        }
+        Ok(key)
+    }
+}
+
+// Case folding applied by AlertKey::new. Canonical keys and components are already folded, so
+// folding user input finds them in whatever case it was typed.
+pub fn fold(value: &str) -> String {
+    value.to_ascii_lowercase()
+}
+
+impl fmt::Display for AlertKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(namespace) = &self.namespace {
+            write!(f, "{}{}", namespace, NAMESPACE_SEPARATOR)?;
+        }
+        write!(
+            f,
+            "{}{}{}{}{}",
+            escape(&self.blockchain),
+            SEPARATOR,
+            escape(&self.protocol),
+            SEPARATOR,
+            escape(&self.method)
+        )
+    }
+}
+
+fn escape(component: &str) -> String {
+    component.replace(SEPARATOR, ESCAPED_SEPARATOR)
+}
+
+fn canonical_component(name: &str, value: &str, max_len: usize) -> Result<String, String> {
+    if value.is_empty() || value.len() > max_len {
+        return Err(format!("{} must be 1 to {} characters", name, max_len));
+    }
+    let value = value.to_ascii_lowercase();
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-.".contains(c))
+    {
+        return Err(format!(
+            "{} may only contain letters, digits, _, - and .",
+            name
+        ));
+    }
+    Ok(value)
+}
+
+fn canonical_namespace(value: &str) -> Result<String, String> {
+    // Namespaces are addresses, so they aren't bound by the scheme
+    let value = canonical_component("namespace", value, MAX_COMPONENT_LEN)?;
+    if value.contains(SEPARATOR) {
+        return Err("namespace may not contain .".to_string());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(
+        namespace: Option<&str>,
+        blockchain: &str,
+        protocol: &str,
+        method: &str,
+    ) -> Result<AlertKey, ContractError> {
+        AlertKey::new(
+            &KeyScheme::default(),
+            namespace,
+            blockchain,
+            protocol,
+            method,
+        )
+    }
+
+    fn parse(alert_key: &str) -> Result<AlertKey, ContractError> {
+        AlertKey::parse(&KeyScheme::default(), alert_key)
+    }
+
+    #[test]
+    fn keys_are_case_folded() {
+        let key = new(None, "Terra", "Anchor", "liquidation").unwrap();
+        assert_eq!(key.to_string(), "terra.anchor.liquidation");
+        assert_eq!(key, new(None, "terra", "ANCHOR", "Liquidation").unwrap());
+    }
+
+    #[test]
+    fn separators_are_escaped_and_parsed_back() {
+        let key = new(Some("alice"), "terra", "anchor", "v1.2").unwrap();
+        assert_eq!(key.to_string(), "alice/terra.anchor.v1%2e2");
+        assert_eq!(parse("alice/terra.anchor.v1%2e2").unwrap(), key);
+
+        // Without escaping these two would share the key terra.a.b.c
+        assert_ne!(
+            new(None, "terra.a", "b", "c").unwrap().to_string(),
+            new(None, "terra", "a.b", "c").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_components() {
+        for (namespace, blockchain) in &[
+            (None, ""),
+            (None, "ter ra"),
+            (None, "terra%2e"),
+            (None, "terra/x"),
+            (Some("ali.ce"), "terra"),
+            (Some(""), "terra"),
+        ] {
+            match new(*namespace, blockchain, "anchor", "liquidation") {
+                Err(ContractError::InvalidAlertKey { .. }) => {}
+                res => panic!("Must return InvalidAlertKey, got {:?}", res),
+            }
+        }
+        assert!(new(None, &"a".repeat(MAX_COMPONENT_LEN + 1), "b", "c").is_err());
+    }
+
+    #[test]
+    fn folding_gives_the_canonical_key() {
+        let key = new(Some("Alice"), "Terra", "Anchor", "V1.2").unwrap();
+        assert_eq!(fold("ALICE/Terra.Anchor.v1%2E2"), key.to_string());
+        assert_eq!(fold(&key.to_string()), key.to_string());
+    }
+
+    #[test]
+    fn canonical_keys_round_trip() {
+        for (namespace, method) in &[
+            (None, "liquidation"),
+            (Some("terra1alice"), "liquidation"),
+            (None, "v1.2.3"),
+            (Some("terra1alice"), ".borrow."),
+        ] {
+            let key = new(*namespace, "terra", "anchor", method).unwrap();
+            let parsed = parse(&key.to_string()).unwrap();
+            assert_eq!(parsed.namespace.as_deref(), *namespace);
+            assert_eq!(parsed.method, *method);
+            assert_eq!(parsed, key);
+        }
+    }
+
+    #[test]
+    fn only_canonical_keys_parse() {
+        for alert_key in &[
+            "Terra.Anchor.liquidation",
+            "terra.anchor",
+            "terra.anchor.liquidation.x",
+            "terra.anchor.v1%2E2",
+            "alice/bob/terra.anchor.liquidation",
+        ] {
+            assert!(parse(alert_key).is_err(), "{}", alert_key);
+        }
+    }
+
+    #[test]
+    fn schemes_bound_component_length() {
+        let scheme = KeyScheme {
+            namespace_creators: true,
+            max_component_len: 5,
+        };
+        assert!(AlertKey::new(&scheme, None, "terra", "mir", "price").is_ok());
+        assert!(AlertKey::new(&scheme, None, "terra", "anchor", "price").is_err());
+        assert!(AlertKey::parse(&scheme, "terra.anchor.price").is_err());
+        // Namespaces are addresses and keep the full length
+        assert!(AlertKey::new(&scheme, Some("terra1alice"), "terra", "mir", "price").is_ok());
+    }
+}
@@ -1,13 +1,17 @@
 pub mod contract;
+pub mod error;
+pub mod events;
+pub mod keys;
 pub mod models;
 pub mod msg;
 pub mod state;
+pub mod validation;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
     use super::contract;
     use cosmwasm_std::{
-        do_handle, do_init, do_query, ExternalApi, ExternalQuerier, ExternalStorage,
+        do_handle, do_init, do_migrate, do_query, ExternalApi, ExternalQuerier, ExternalStorage,
     };
 
     #[no_mangle]
@@ -28,6 +32,15 @@ mod wasm {
         )
     }
 
+    #[no_mangle]
+    extern "C" fn migrate(env_ptr: u32, msg_ptr: u32) -> u32 {
+        do_migrate(
+            &contract::migrate::<ExternalStorage, ExternalApi, ExternalQuerier>,
+            env_ptr,
+            msg_ptr,
+        )
+    }
+
     #[no_mangle]
     extern "C" fn query(msg_ptr: u32) -> u32 {
         do_query(
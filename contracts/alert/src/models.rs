@@ -1,4 +1,4 @@
-use cosmwasm_std::Order;
+use cosmwasm_std::{Binary, Coin, HumanAddr, Order, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +11,77 @@ pub struct Alert {
     pub name: String,
     pub description: String,
     pub fields: Vec<AlertField>,
+    // Empty for alerts created before creators were recorded
+    #[serde(default)]
+    pub creator: HumanAddr,
+    // Deprecated alerts keep their subscriptions but can't be subscribed to anymore
+    #[serde(default)]
+    pub deprecated: bool,
+    // Subscribing is free when no price is set
+    #[serde(default)]
+    pub price: Option<AlertPrice>,
+    // The template the fields were taken from, None for alerts defined from scratch
+    #[serde(default)]
+    pub template: Option<AlertTemplateRef>,
+    // Alerts created before severities were recorded are informational
+    #[serde(default)]
+    pub severity: Severity,
+    // A single topic like `lending`, following the same rules as tags, see GetAlertsByCategory
+    #[serde(default)]
+    pub category: Option<String>,
+    // Lower case keywords like `liquidation`, see GetAlertsByTag
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// How urgent it is to act on a notification of the alert
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    // Name used in storage keys and logs, the same as in JSON
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+// Reusable field definitions, alerts created from a template start with a copy of its fields
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AlertTemplate {
+    pub template_id: u64,
+    pub name: String,
+    // Used for alerts created from the template without a description of their own
+    pub description: String,
+    pub fields: Vec<AlertField>,
+    pub creator: HumanAddr,
+    // Starts at 1 and is incremented whenever fields are added
+    pub version: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AlertTemplateRef {
+    pub template_id: u64,
+    // Version of the template the alert has all fields of
+    pub version: u64,
+}
+
+// Price of a subscription, paid in a native coin once per billing period
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AlertPrice {
+    pub denom: String,
+    pub amount: Uint128,
+    // Length of a billing period in seconds
+    pub billing_period: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -18,13 +89,164 @@ pub struct AlertField {
     pub field_key: String,
     pub name: String,
     pub description: String,
+    // Alerts created before fields were typed are treated as free-form text
+    #[serde(default)]
+    pub field_type: AlertFieldType,
+    // Checked against the normalized value, use an empty string to accept any value
     pub validation_regex: String,
+    // Subscribers may leave optional fields out
+    #[serde(default)]
+    pub optional: bool,
+}
+
+// The type of value a subscriber has to provide for an AlertField
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertFieldType {
+    // Any string, only checked against the validation regex
+    #[default]
+    Text,
+    // A bech32 account address, e.g. terra1...
+    Address,
+    // An unsigned integer amount, e.g. 1000000
+    Uint128,
+    // A non-negative decimal with up to 18 fractional digits, e.g. 1.5
+    Decimal,
+    // A decimal between 0 and 100 inclusive, e.g. 75.5
+    Percentage,
+    // A native coin denom, e.g. uusd
+    Denom,
+    // One of a fixed list of options
+    Enum {
+        options: Vec<String>,
+    },
+    // `true` or `false`
+    Bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Subscription {
+    // An address can have several subscriptions to the same alert, told apart by their id
+    #[serde(default)]
+    pub subscription_id: u64,
     pub alert_key: String,
     pub field_values: Vec<SubscriptionFieldValue>,
+    // Block time in seconds, 0 for subscriptions created before it was recorded
+    #[serde(default)]
+    pub created_at: u64,
+    // Block time in seconds at which the subscription lapses, never for free alerts
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    // Names of the subscriber's channels to deliver notifications to
+    #[serde(default)]
+    pub channels: Vec<String>,
+    // Deposit paid when subscribing, refunded on unsubscribe
+    #[serde(default)]
+    pub deposit: Option<Coin>,
+}
+
+impl Subscription {
+    pub fn is_expired(&self, time: u64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= time)
+    }
+}
+
+// Anti-spam limits applied when subscribing, set by the owner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionLimits {
+    pub max_subscriptions_per_address: u64,
+    pub max_field_values: u64,
+    // Maximum length of a single field value in bytes
+    pub max_value_len: u64,
+    // Locked per subscription and refunded when unsubscribing, none when not set
+    pub deposit: Option<Coin>,
+}
+
+impl Default for SubscriptionLimits {
+    fn default() -> Self {
+        SubscriptionLimits {
+            max_subscriptions_per_address: 100,
+            max_field_values: 20,
+            max_value_len: 256,
+            deposit: None,
+        }
+    }
+}
+
+// How alert keys are derived, see `keys`. Set at init and fixed afterwards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyScheme {
+    // Prefix the keys of alerts created by anyone but the owner with the creator address
+    pub namespace_creators: bool,
+    // Maximum length of the blockchain, protocol and method in bytes, at most 64
+    pub max_component_len: u64,
+}
+
+impl Default for KeyScheme {
+    fn default() -> Self {
+        KeyScheme {
+            namespace_creators: true,
+            max_component_len: 64,
+        }
+    }
+}
+
+// A subscription as seen from its alert, used by the notifier
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AlertSubscriber {
+    pub subscription_id: u64,
+    pub subscriber_addr: HumanAddr,
+    pub field_values: Vec<SubscriptionFieldValue>,
+    pub expires_at: Option<u64>,
+    pub channels: Vec<String>,
+}
+
+// Where notifications are delivered. The contact handle (email address, chat id, webhook URL)
+// is encrypted for the notifier's public key, so only the dispatcher can read it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Channel {
+    pub name: String,
+    pub channel_type: ChannelType,
+    pub ciphertext: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelType {
+    Email,
+    Telegram,
+    Discord,
+    Webhook,
+}
+
+// A report that an alert fired for one subscription, kept in the inbox of the subscriber
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Notification {
+    pub notification_id: u64,
+    pub subscription_id: u64,
+    pub alert_key: String,
+    // Free-form details of what happened, as sent by the reporter
+    pub payload: String,
+    // Block (height or hash) on the alert's blockchain that triggered the alert
+    pub block_ref: String,
+    pub reporter: HumanAddr,
+    // Block time in seconds when the trigger was reported
+    pub reported_at: u64,
+    // Set once the subscriber acknowledged the notification
+    #[serde(default)]
+    pub read: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlockchainInfo {
+    pub blockchain: String,
+    pub alert_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolInfo {
+    pub protocol: String,
+    pub alert_count: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -44,9 +266,9 @@ pub enum OrderBy {
 }
 
 // Converts our custom OrderBy to cosmwasm_std::Order;
-impl Into<Order> for OrderBy {
-    fn into(self) -> Order {
-        if self == OrderBy::Asc {
+impl From<OrderBy> for Order {
+    fn from(order_by: OrderBy) -> Order {
+        if order_by == OrderBy::Asc {
             Order::Ascending
         } else {
             Order::Descending
@@ -1,14 +1,37 @@
-use crate::models::{Alert, AlertField, OrderBy, Subscription, SubscriptionFieldValue};
-use cosmwasm_std::HumanAddr;
+use crate::models::{
+    Alert, AlertField, AlertPrice, AlertSubscriber, AlertTemplate, BlockchainInfo, Channel,
+    ChannelType, KeyScheme, Notification, OrderBy, ProtocolInfo, Severity, Subscription,
+    SubscriptionFieldValue, SubscriptionLimits,
+};
+use cosmwasm_std::{Binary, HumanAddr};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InitMsg {}
+pub struct InitMsg {
+    pub treasury: Option<HumanAddr>,
+    // Share of subscription payments sent to the treasury, 0 by default
+    pub treasury_fee_percent: Option<u64>,
+    // Anti-spam limits, the defaults of SubscriptionLimits apply when not given
+    pub limits: Option<SubscriptionLimits>,
+    // Alert key derivation, fixed once set, the defaults of KeyScheme apply when not given
+    pub key_scheme: Option<KeyScheme>,
+}
+
+// Migrations bring the storage to the layout of this code. Send MigrateMsg again until the
+// migrate log reports complete as true.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    // Records migrated by this message, 500 by default
+    pub limit: Option<u32>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
+    // The key is derived from blockchain, protocol and method, see `keys::AlertKey`.
+    // With a template, `fields` override template fields with the same key and are appended
+    // otherwise, and an empty description is replaced by the template description.
     CreateAlert {
         blockchain: String,
         protocol: String,
@@ -16,13 +39,143 @@ pub enum HandleMsg {
         name: String,
         description: String,
         fields: Vec<AlertField>,
+        price: Option<AlertPrice>,
+        #[serde(default)]
+        template_id: Option<u64>,
+        #[serde(default)]
+        severity: Severity,
+        category: Option<String>,
+        // Folded to lower case, at most 10
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    // Only callable by the alert creator or the owner
+    UpdateAlert {
+        alert_key: String,
+        name: Option<String>,
+        description: Option<String>,
+        // Appended to the existing fields, must all be optional
+        new_fields: Option<Vec<AlertField>>,
+        severity: Option<Severity>,
+        // Replaces the category, an empty string removes it
+        category: Option<String>,
+        // Replaces all tags
+        tags: Option<Vec<String>>,
+    },
+    DeprecateAlert {
+        alert_key: String,
+    },
+    // Removes a limited number of subscriptions per call, refunding their deposits. The alert is
+    // deprecated until its last subscription is removed, repeat until remaining_subscriptions is 0
+    DeleteAlert {
+        alert_key: String,
+    },
+    // Adds the fields the template gained since the alert was created, or last brought up to date
+    ApplyTemplate {
+        alert_key: String,
+    },
+    // Use no price to make the alert free, only applies to new subscriptions
+    SetAlertPrice {
+        alert_key: String,
+        price: Option<AlertPrice>,
+    },
+    // Only callable by the owner and creators, the template id is returned in the `template_id` log
+    CreateTemplate {
+        name: String,
+        description: String,
+        fields: Vec<AlertField>,
+    },
+    // Only callable by the template creator or the owner. New fields are appended and must all
+    // be optional, adding fields increments the template version.
+    UpdateTemplate {
+        template_id: u64,
+        name: Option<String>,
+        description: Option<String>,
+        new_fields: Option<Vec<AlertField>>,
+    },
+    // Fails with template_in_use while alerts derived from the template exist
+    DeleteTemplate {
+        template_id: u64,
     },
+    // Has to be sent with at least the alert price, anything above it is refunded.
+    // Every call creates a new subscription, its id is returned in the `subscription_id` log.
     SubscribeAlert {
         alert_key: String,
         field_values: Vec<SubscriptionFieldValue>,
+        // Names of channels registered with SetChannel
+        #[serde(default)]
+        channels: Vec<String>,
+    },
+    // Replaces the field values of an existing subscription, validated like in SubscribeAlert.
+    // The channels are kept when not given.
+    UpdateSubscription {
+        subscription_id: u64,
+        field_values: Vec<SubscriptionFieldValue>,
+        channels: Option<Vec<String>>,
+    },
+    // Pays for another billing period, counted from the current expiry if not yet expired
+    RenewSubscription {
+        subscription_id: u64,
     },
     UnsubscribeAlert {
+        subscription_id: u64,
+    },
+    // Only callable by reporters, stores a notification in the inbox of the subscriber.
+    // Only the latest MAX_NOTIFICATIONS notifications of a subscriber are kept.
+    ReportTrigger {
         alert_key: String,
+        subscriber: HumanAddr,
+        subscription_id: u64,
+        payload: String,
+        block_ref: String,
+    },
+    // Admin messages, only callable by the owner
+    AddCreator {
+        creator: HumanAddr,
+    },
+    // Removed creators can no longer manage their alerts and templates, and the creator share
+    // of their subscription payments goes to the owner
+    RemoveCreator {
+        creator: HumanAddr,
+    },
+    // Proposes a new owner, replacing any earlier proposal. The owner only changes once the
+    // proposed address sends AcceptOwnership.
+    TransferOwnership {
+        new_owner: HumanAddr,
+    },
+    // Only callable by the proposed owner
+    AcceptOwnership {},
+    // Adds or replaces a delivery channel of the sender. The ciphertext has to be encrypted
+    // for the key returned by GetNotifierPubkey.
+    SetChannel {
+        name: String,
+        channel_type: ChannelType,
+        ciphertext: Binary,
+    },
+    // Subscriptions still naming the channel are no longer delivered to it
+    RemoveChannel {
+        name: String,
+    },
+    // Marks notifications of the sender as read
+    AckNotifications {
+        ids: Vec<u64>,
+    },
+    AddReporter {
+        reporter: HumanAddr,
+    },
+    RemoveReporter {
+        reporter: HumanAddr,
+    },
+    // Channels encrypted for a previous key have to be set again by their owners
+    SetNotifierPubkey {
+        pubkey: Binary,
+    },
+    UpdateTreasury {
+        treasury: Option<HumanAddr>,
+        treasury_fee_percent: u64,
+    },
+    UpdateLimits {
+        limits: SubscriptionLimits,
     },
 }
 
@@ -30,24 +183,251 @@ pub enum HandleMsg {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetAlerts {
-        start_after: Option<HumanAddr>,
+        // next_cursor of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Alerts on a blockchain, or only those of one protocol when `protocol` is set, both matched
+    // in any case
+    GetAlertsBy {
+        blockchain: String,
+        protocol: Option<String>,
+        // next_cursor of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Alerts with a tag, across blockchains and protocols
+    GetAlertsByTag {
+        tag: String,
+        // next_cursor of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Alerts in a category, matched in any case
+    GetAlertsByCategory {
+        category: String,
+        // next_cursor of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    GetAlertsBySeverity {
+        severity: Severity,
+        // next_cursor of the previous page
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    ListBlockchains {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Blockchains and protocols are listed in lower case, the blockchain is matched in any case
+    ListProtocols {
+        blockchain: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Fails with alert_not_found if the alert doesn't exist, the key is matched in any case
+    GetAlert {
+        alert_key: String,
+    },
+    // Fails with template_not_found if the template doesn't exist
+    GetTemplate {
+        template_id: u64,
+    },
+    ListTemplates {
+        // next_cursor of the previous page
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Alerts created from a template, or only those missing fields of its latest version. Pages
+    // of outdated alerts can be short or empty, keep paging until next_cursor is null.
+    GetAlertsByTemplate {
+        template_id: u64,
+        outdated_only: Option<bool>,
+        // next_cursor of the previous page
+        start_after: Option<String>,
         limit: Option<u32>,
         order_by: Option<OrderBy>,
     },
     GetSubscriptionsForAddress {
         subscriber_addr: HumanAddr,
-        start_after: Option<HumanAddr>,
+        // next_cursor of the previous page
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // Fails with not_subscribed if the address has no subscription with this id
+    GetSubscription {
+        subscriber_addr: HumanAddr,
+        subscription_id: u64,
+    },
+    // False once all subscriptions of the address to the alert expired
+    IsSubscribed {
+        subscriber_addr: HumanAddr,
+        alert_key: String,
+        // Block time in seconds to check expiry at, queries have no access to the current block
+        at_time: u64,
+    },
+    // Leaves out expired subscriptions, so pages can be short or empty, keep paging until
+    // next_cursor is null
+    GetSubscribersForAlert {
+        alert_key: String,
+        // Same as in IsSubscribed
+        at_time: u64,
+        // next_cursor of the previous page
+        start_after: Option<u64>,
         limit: Option<u32>,
         order_by: Option<OrderBy>,
     },
+    GetNotifications {
+        subscriber: HumanAddr,
+        unread_only: Option<bool>,
+        // next_cursor of the previous page
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order_by: Option<OrderBy>,
+    },
+    // First page of subscriptions and latest notifications of an address, for dashboards
+    GetSubscriberOverview {
+        subscriber_addr: HumanAddr,
+        limit: Option<u32>,
+    },
+    GetChannels {
+        subscriber_addr: HumanAddr,
+    },
+    GetNotifierPubkey {},
+    GetCreators {},
+    GetReporters {},
+    GetTreasury {},
+    GetLimits {},
+    GetKeyScheme {},
+    // Totals, plus the number of subscriptions of an address when given
+    GetStats {
+        subscriber_addr: Option<HumanAddr>,
+    },
+    GetAlertStats {
+        alert_key: String,
+    },
 }
 
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetAlertsResponse {
     pub alerts: Vec<Alert>,
+    // Pass as start_after to get the next page, None if this is the last page
+    pub next_cursor: Option<String>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListBlockchainsResponse {
+    pub blockchains: Vec<BlockchainInfo>,
+    pub next_cursor: Option<String>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListProtocolsResponse {
+    pub protocols: Vec<ProtocolInfo>,
+    pub next_cursor: Option<String>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetAlertResponse {
+    pub alert: Alert,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetTemplateResponse {
+    pub template: AlertTemplate,
+    // Number of alerts created from the template
+    pub alert_count: u64,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListTemplatesResponse {
+    pub templates: Vec<AlertTemplate>,
+    pub next_cursor: Option<u64>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetSubscriptionsForAddressResponse {
     pub subscriptions: Vec<Subscription>,
+    pub next_cursor: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetSubscriptionResponse {
+    pub subscription: Subscription,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IsSubscribedResponse {
+    pub is_subscribed: bool,
+    // Ids of the subscriptions that haven't expired
+    pub subscription_ids: Vec<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetSubscribersForAlertResponse {
+    pub subscribers: Vec<AlertSubscriber>,
+    pub next_cursor: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetCreatorsResponse {
+    pub owner: HumanAddr,
+    // Proposed with TransferOwnership and not yet accepted
+    pub pending_owner: Option<HumanAddr>,
+    pub creators: Vec<HumanAddr>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetNotificationsResponse {
+    pub notifications: Vec<Notification>,
+    pub next_cursor: Option<u64>,
+    pub unread_count: u64,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetSubscriberOverviewResponse {
+    // Newest first, continue with GetSubscriptionsForAddress
+    pub subscriptions: Vec<Subscription>,
+    pub subscriptions_next_cursor: Option<u64>,
+    // Newest first, continue with GetNotifications
+    pub notifications: Vec<Notification>,
+    pub notifications_next_cursor: Option<u64>,
+    pub unread_count: u64,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetChannelsResponse {
+    pub channels: Vec<Channel>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetNotifierPubkeyResponse {
+    pub pubkey: Option<Binary>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetReportersResponse {
+    pub reporters: Vec<HumanAddr>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetTreasuryResponse {
+    pub treasury: Option<HumanAddr>,
+    pub treasury_fee_percent: u64,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetLimitsResponse {
+    pub limits: SubscriptionLimits,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetKeySchemeResponse {
+    pub key_scheme: KeyScheme,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetStatsResponse {
+    pub total_alerts: u64,
+    pub total_subscriptions: u64,
+    pub address_subscriptions: Option<u64>,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetAlertStatsResponse {
+    pub alert_key: String,
+    // Expired subscriptions count until they are removed
+    pub subscription_count: u64,
+    pub subscriber_count: u64,
 }
@@ -2,15 +2,42 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str;
 
-use crate::models::{Alert, OrderBy, Subscription};
-use cosmwasm_std::{CanonicalAddr, StdResult, Storage};
-use cosmwasm_storage::{singleton, singleton_read, Bucket, ReadonlyBucket};
+use crate::keys::fold;
+use crate::models::{
+    Alert, AlertTemplate, Channel, KeyScheme, Notification, OrderBy, Severity, Subscription,
+    SubscriptionLimits,
+};
+use cosmwasm_std::{Binary, CanonicalAddr, Order, StdError, StdResult, Storage};
+use cosmwasm_storage::{singleton, singleton_read, to_length_prefixed, Bucket, ReadonlyBucket};
 
 pub static PREFIX_CONFIG: &[u8] = b"config";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: CanonicalAddr,
+    // Addresses other than the owner that are allowed to create alerts
+    #[serde(default)]
+    pub creators: Vec<CanonicalAddr>,
+    // Receives `treasury_fee_percent` of every subscription payment, the rest goes to the
+    // alert creator. Creators receive the full payment while no treasury is set.
+    #[serde(default)]
+    pub treasury: Option<CanonicalAddr>,
+    #[serde(default)]
+    pub treasury_fee_percent: u64,
+    // Addresses allowed to report that an alert fired for a subscriber
+    #[serde(default)]
+    pub reporters: Vec<CanonicalAddr>,
+    // Channels are encrypted for this key, None until the owner registers the notifier
+    #[serde(default)]
+    pub notifier_pubkey: Option<Binary>,
+    #[serde(default)]
+    pub limits: SubscriptionLimits,
+    // Proposed new owner, who has to accept before ownership changes
+    #[serde(default)]
+    pub pending_owner: Option<CanonicalAddr>,
+    // Contracts created before keys were configurable use the default scheme
+    #[serde(default)]
+    pub key_scheme: KeyScheme,
 }
 
 pub fn store_config<S: Storage>(storage: &mut S, config: &Config) -> StdResult<()> {
@@ -22,126 +49,1351 @@ pub fn read_config<S: Storage>(storage: &S) -> StdResult<Config> {
 }
 
 static PREFIX_ALERT: &[u8] = b"alert";
+// Key of every alert under its folded form, see `keys::fold`. Alerts created before keys were
+// canonicalized can share a folded key, the first one keeps it.
+static PREFIX_FOLDED_KEY: &[u8] = b"folded_key";
+// Secondary indexes of alerts, stored under `PREFIX_BLOCKCHAIN_ALERT/<blockchain>/<alert_key>`
+// and `PREFIX_PROTOCOL_ALERT/<blockchain>/<protocol>/<alert_key>` with empty values. Blockchains
+// and protocols are folded, so alerts created before keys were canonicalized are found as well.
+static PREFIX_BLOCKCHAIN_ALERT: &[u8] = b"blockchain_alert";
+static PREFIX_PROTOCOL_ALERT: &[u8] = b"protocol_alert";
+// Number of alerts per blockchain, and per protocol under `PREFIX_PROTOCOL_COUNT/<blockchain>`
+static PREFIX_BLOCKCHAIN_COUNT: &[u8] = b"blockchain_count";
+static PREFIX_PROTOCOL_COUNT: &[u8] = b"protocol_count";
+// Alerts created from a template, stored under `PREFIX_TEMPLATE_ALERT/<template_id>/<alert_key>`
+// with empty values, and their number per template
+static PREFIX_TEMPLATE_ALERT: &[u8] = b"template_alert";
+static PREFIX_TEMPLATE_COUNT: &[u8] = b"template_count";
+// Alerts with a tag, category or severity, stored under `PREFIX_TAG_ALERT/<tag>/<alert_key>`,
+// `PREFIX_CATEGORY_ALERT/<category>/<alert_key>` and `PREFIX_SEVERITY_ALERT/<severity>/<alert_key>`
+// with empty values
+static PREFIX_TAG_ALERT: &[u8] = b"tag_alert";
+static PREFIX_CATEGORY_ALERT: &[u8] = b"category_alert";
+static PREFIX_SEVERITY_ALERT: &[u8] = b"severity_alert";
+// Contract wide totals, keyed by what they count
+static PREFIX_TOTAL: &[u8] = b"total";
+const TOTAL_ALERTS: &[u8] = b"alerts";
+const TOTAL_SUBSCRIPTIONS: &[u8] = b"subscriptions";
 
 pub fn store_alert<S: Storage>(storage: &mut S, alert: &Alert) -> StdResult<()> {
+    // The blockchain, protocol and template of an alert never change, so only new alerts need
+    // indexing. Tags, category and severity can be replaced and are indexed again when they
+    // change.
+    match may_load_alert(storage, alert.alert_key.as_bytes())? {
+        None => update_alert_indexes(storage, alert, true)?,
+        Some(stored)
+            if stored.tags != alert.tags
+                || stored.category != alert.category
+                || stored.severity != alert.severity =>
+        {
+            update_label_indexes(storage, &stored, false)?;
+            update_label_indexes(storage, alert, true)?;
+        }
+        Some(_) => {}
+    }
+
     let mut bucket: Bucket<S, Alert> = Bucket::new(PREFIX_ALERT, storage);
     bucket.save(alert.alert_key.as_bytes(), alert)
 }
 
+pub fn remove_alert<S: Storage>(storage: &mut S, alert_key: &String) -> StdResult<()> {
+    if let Some(alert) = may_load_alert(storage, alert_key.as_bytes())? {
+        update_alert_indexes(storage, &alert, false)?;
+    }
+
+    let mut bucket: Bucket<S, Alert> = Bucket::new(PREFIX_ALERT, storage);
+    bucket.remove(alert_key.as_bytes());
+    Ok(())
+}
+
+// Adds an alert to, or removes it from, the folded key, blockchain, protocol, template, tag,
+// category and severity indexes and counts as well as the total
+fn update_alert_indexes<S: Storage>(storage: &mut S, alert: &Alert, add: bool) -> StdResult<()> {
+    let folded_blockchain: String = fold(&alert.blockchain);
+    let folded_protocol: String = fold(&alert.protocol);
+    let blockchain: &[u8] = folded_blockchain.as_bytes();
+    let protocol: &[u8] = folded_protocol.as_bytes();
+    let alert_key: &[u8] = alert.alert_key.as_bytes();
+
+    let folded_key: String = fold(&alert.alert_key);
+    let mut folded_bucket: Bucket<S, String> = Bucket::new(PREFIX_FOLDED_KEY, storage);
+    match folded_bucket.may_load(folded_key.as_bytes())? {
+        None if add => folded_bucket.save(folded_key.as_bytes(), &alert.alert_key)?,
+        Some(stored) if !add && stored == alert.alert_key => {
+            folded_bucket.remove(folded_key.as_bytes())
+        }
+        _ => {}
+    }
+
+    let mut blockchain_bucket: Bucket<S, bool> =
+        Bucket::multilevel(&[PREFIX_BLOCKCHAIN_ALERT, blockchain], storage);
+    if add {
+        blockchain_bucket.save(alert_key, &true)?;
+    } else {
+        blockchain_bucket.remove(alert_key);
+    }
+
+    let mut protocol_bucket: Bucket<S, bool> =
+        Bucket::multilevel(&[PREFIX_PROTOCOL_ALERT, blockchain, protocol], storage);
+    if add {
+        protocol_bucket.save(alert_key, &true)?;
+    } else {
+        protocol_bucket.remove(alert_key);
+    }
+
+    update_count(
+        Bucket::new(PREFIX_BLOCKCHAIN_COUNT, storage),
+        blockchain,
+        add,
+    )?;
+    update_count(
+        Bucket::multilevel(&[PREFIX_PROTOCOL_COUNT, blockchain], storage),
+        protocol,
+        add,
+    )?;
+
+    if let Some(template) = &alert.template {
+        let template_id: [u8; 8] = template.template_id.to_be_bytes();
+        let mut template_bucket: Bucket<S, bool> =
+            Bucket::multilevel(&[PREFIX_TEMPLATE_ALERT, &template_id], storage);
+        if add {
+            template_bucket.save(alert_key, &true)?;
+        } else {
+            template_bucket.remove(alert_key);
+        }
+        update_count(
+            Bucket::new(PREFIX_TEMPLATE_COUNT, storage),
+            &template_id,
+            add,
+        )?;
+    }
+    update_label_indexes(storage, alert, add)?;
+
+    update_count(Bucket::new(PREFIX_TOTAL, storage), TOTAL_ALERTS, add)
+}
+
+// Indexes of the tags, category and severity, which can change after an alert was created
+fn update_label_indexes<S: Storage>(storage: &mut S, alert: &Alert, add: bool) -> StdResult<()> {
+    let mut labels: Vec<(&[u8], &[u8])> = alert
+        .tags
+        .iter()
+        .map(|tag| (PREFIX_TAG_ALERT, tag.as_bytes()))
+        .collect();
+    if let Some(category) = &alert.category {
+        labels.push((PREFIX_CATEGORY_ALERT, category.as_bytes()));
+    }
+    labels.push((PREFIX_SEVERITY_ALERT, alert.severity.as_str().as_bytes()));
+
+    for (prefix, label) in labels {
+        let mut bucket: Bucket<S, bool> = Bucket::multilevel(&[prefix, label], storage);
+        if add {
+            bucket.save(alert.alert_key.as_bytes(), &true)?;
+        } else {
+            bucket.remove(alert.alert_key.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+// Increments or decrements a counter, removing it once it reaches zero
+fn update_count<S: Storage>(
+    mut bucket: Bucket<S, u64>,
+    key: &[u8],
+    increment: bool,
+) -> StdResult<()> {
+    let count: u64 = bucket.may_load(key)?.unwrap_or(0);
+    let count: u64 = if increment {
+        count + 1
+    } else {
+        count.saturating_sub(1)
+    };
+    if count == 0 {
+        bucket.remove(key);
+        Ok(())
+    } else {
+        bucket.save(key, &count)
+    }
+}
+
 // Retrieve an alert by its key
 pub fn read_alert<S: Storage>(storage: &S, alert_key: &String) -> StdResult<Alert> {
-    let bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, &storage);
+    let bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
     bucket.load(alert_key.as_bytes())
 }
 
+// Retrieve an alert by its key in any case, returns None if it doesn't exist
+pub fn may_read_alert<S: Storage>(storage: &S, alert_key: &str) -> StdResult<Option<Alert>> {
+    match find_alert_key(storage, alert_key)? {
+        Some(alert_key) => may_load_alert(storage, alert_key.as_bytes()),
+        None => Ok(None),
+    }
+}
+
+// Returns the key an alert is stored under, given its key in any case. Exact matches go first,
+// so of two legacy alerts differing only in case each one stays reachable by its own key.
+pub fn find_alert_key<S: Storage>(storage: &S, alert_key: &str) -> StdResult<Option<String>> {
+    if may_load_alert(storage, alert_key.as_bytes())?.is_some() {
+        return Ok(Some(alert_key.to_string()));
+    }
+    let bucket: ReadonlyBucket<S, String> = ReadonlyBucket::new(PREFIX_FOLDED_KEY, storage);
+    bucket.may_load(fold(alert_key).as_bytes())
+}
+
+fn may_load_alert<S: Storage>(storage: &S, alert_key: &[u8]) -> StdResult<Option<Alert>> {
+    let bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
+    bucket.may_load(alert_key)
+}
+
 // settings for pagination
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
+
+// A page of items and the cursor to start the next page after, None on the last page
+pub type Page<T, C> = (Vec<T>, Option<C>);
+
+// Retrieve a page of alerts, returns the alert_key to start the next page after
 pub fn read_alerts<S: Storage>(
     storage: &S,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    let limit = calc_limit(limit);
+    let (start, end, order) = calc_range(start_after.as_ref().map(|k| k.as_bytes()), order);
 
-    start_after: Option<CanonicalAddr>, // Kinda like a cursor for pagination
+    let alerts_bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
+    let mut alerts: Vec<Alert> = alerts_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
+        .map(|item| {
+            let (_k, v) = item?;
+            Ok(v)
+        })
+        .collect::<StdResult<Vec<Alert>>>()?;
+    let next_cursor = split_page(&mut alerts, limit, |alert| alert.alert_key.clone());
+
+    Ok((alerts, next_cursor))
+}
+
+// Retrieve a page of alerts on a blockchain, or only those of one of its protocols, both in any
+// case
+pub fn read_alerts_by<S: Storage>(
+    storage: &S,
+    blockchain: &str,
+    protocol: Option<&str>,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
     limit: Option<u32>,
     order: Option<OrderBy>,
-) -> StdResult<Vec<Alert>> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let (start, end, order) = match order {
-        Some(OrderBy::Asc) => (calc_range_start(start_after), None, OrderBy::Asc),
-        _ => (None, calc_range_end(start_after), OrderBy::Desc),
+) -> StdResult<Page<Alert, String>> {
+    let limit = calc_limit(limit);
+    let (start, end, order) = calc_range(start_after.as_ref().map(|k| k.as_bytes()), order);
+
+    let blockchain: String = fold(blockchain);
+    let index_bucket: ReadonlyBucket<S, bool> = match protocol.map(fold) {
+        Some(protocol) => ReadonlyBucket::multilevel(
+            &[
+                PREFIX_PROTOCOL_ALERT,
+                blockchain.as_bytes(),
+                protocol.as_bytes(),
+            ],
+            storage,
+        ),
+        None => {
+            ReadonlyBucket::multilevel(&[PREFIX_BLOCKCHAIN_ALERT, blockchain.as_bytes()], storage)
+        }
     };
+    let alerts_bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
+    let mut alerts: Vec<Alert> = index_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
+        .map(|item| {
+            let (k, _v) = item?;
+            alerts_bucket.load(&k)
+        })
+        .collect::<StdResult<Vec<Alert>>>()?;
+    let next_cursor = split_page(&mut alerts, limit, |alert| alert.alert_key.clone());
 
-    let alerts_bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, &storage);
-    alerts_bucket
-        .range(start.as_deref(), end.as_deref(), order.into())
-        .take(limit)
+    Ok((alerts, next_cursor))
+}
+
+// Retrieve a page of alerts with a tag
+pub fn read_alerts_by_tag<S: Storage>(
+    storage: &S,
+    tag: &str,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    read_alerts_by_label(storage, PREFIX_TAG_ALERT, tag, start_after, limit, order)
+}
+
+// Retrieve a page of alerts in a category
+pub fn read_alerts_by_category<S: Storage>(
+    storage: &S,
+    category: &str,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    read_alerts_by_label(
+        storage,
+        PREFIX_CATEGORY_ALERT,
+        category,
+        start_after,
+        limit,
+        order,
+    )
+}
+
+// Retrieve a page of alerts with a severity
+pub fn read_alerts_by_severity<S: Storage>(
+    storage: &S,
+    severity: &Severity,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    read_alerts_by_label(
+        storage,
+        PREFIX_SEVERITY_ALERT,
+        severity.as_str(),
+        start_after,
+        limit,
+        order,
+    )
+}
+
+fn read_alerts_by_label<S: Storage>(
+    storage: &S,
+    prefix: &[u8],
+    label: &str,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    let limit = calc_limit(limit);
+    let (start, end, order) = calc_range(start_after.as_ref().map(|k| k.as_bytes()), order);
+
+    let index_bucket: ReadonlyBucket<S, bool> =
+        ReadonlyBucket::multilevel(&[prefix, label.as_bytes()], storage);
+    let alerts_bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
+    let mut alerts: Vec<Alert> = index_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
+        .map(|item| {
+            let (k, _v) = item?;
+            alerts_bucket.load(&k)
+        })
+        .collect::<StdResult<Vec<Alert>>>()?;
+    let next_cursor = split_page(&mut alerts, limit, |alert| alert.alert_key.clone());
+
+    Ok((alerts, next_cursor))
+}
+
+// Retrieve a page of blockchains with the number of alerts on each
+pub fn read_blockchain_counts<S: Storage>(
+    storage: &S,
+    start_after: Option<String>, // last blockchain on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<(String, u64), String>> {
+    let bucket: ReadonlyBucket<S, u64> = ReadonlyBucket::new(PREFIX_BLOCKCHAIN_COUNT, storage);
+    read_counts(bucket, start_after, limit, order)
+}
+
+// Retrieve a page of the protocols of a blockchain, given in any case, with the number of alerts
+// of each
+pub fn read_protocol_counts<S: Storage>(
+    storage: &S,
+    blockchain: &str,
+    start_after: Option<String>, // last protocol on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<(String, u64), String>> {
+    let bucket: ReadonlyBucket<S, u64> = ReadonlyBucket::multilevel(
+        &[PREFIX_PROTOCOL_COUNT, fold(blockchain).as_bytes()],
+        storage,
+    );
+    read_counts(bucket, start_after, limit, order)
+}
+
+fn read_counts<S: Storage>(
+    bucket: ReadonlyBucket<S, u64>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<(String, u64), String>> {
+    let limit = calc_limit(limit);
+    let (start, end, order) = calc_range(start_after.as_ref().map(|k| k.as_bytes()), order);
+
+    let mut counts: Vec<(String, u64)> = bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
+        .map(|item| {
+            let (k, count) = item?;
+            let name: &str = str::from_utf8(&k)
+                .map_err(|_| StdError::generic_err("Invalid UTF-8 in storage key"))?;
+            Ok((name.to_string(), count))
+        })
+        .collect::<StdResult<Vec<(String, u64)>>>()?;
+    let next_cursor = split_page(&mut counts, limit, |(name, _)| name.clone());
+
+    Ok((counts, next_cursor))
+}
+
+// Templates are stored under their id, big-endian so they sort in the order they were created
+static PREFIX_TEMPLATE: &[u8] = b"template";
+static PREFIX_TEMPLATE_ID: &[u8] = b"template_id";
+
+pub fn next_template_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    next_id(storage, PREFIX_TEMPLATE_ID)
+}
+
+pub fn store_template<S: Storage>(storage: &mut S, template: &AlertTemplate) -> StdResult<()> {
+    let mut bucket: Bucket<S, AlertTemplate> = Bucket::new(PREFIX_TEMPLATE, storage);
+    bucket.save(&template.template_id.to_be_bytes(), template)
+}
+
+pub fn remove_template<S: Storage>(storage: &mut S, template_id: u64) {
+    let mut bucket: Bucket<S, AlertTemplate> = Bucket::new(PREFIX_TEMPLATE, storage);
+    bucket.remove(&template_id.to_be_bytes());
+}
+
+// Retrieve a template by its id, returns None if it doesn't exist
+pub fn may_read_template<S: Storage>(
+    storage: &S,
+    template_id: u64,
+) -> StdResult<Option<AlertTemplate>> {
+    let bucket: ReadonlyBucket<S, AlertTemplate> = ReadonlyBucket::new(PREFIX_TEMPLATE, storage);
+    bucket.may_load(&template_id.to_be_bytes())
+}
+
+// Retrieve a page of templates, returns the template id to start the next page after
+pub fn read_templates<S: Storage>(
+    storage: &S,
+    start_after: Option<u64>, // template id of the last template on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<AlertTemplate, u64>> {
+    let limit = calc_limit(limit);
+    let start_after: Option<[u8; 8]> = start_after.map(|id| id.to_be_bytes());
+    let (start, end, order) = calc_range(start_after.as_ref().map(|id| &id[..]), order);
+
+    let bucket: ReadonlyBucket<S, AlertTemplate> = ReadonlyBucket::new(PREFIX_TEMPLATE, storage);
+    let mut templates: Vec<AlertTemplate> = bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
         .map(|item| {
             let (_k, v) = item?;
-            Ok(Alert {
-                alert_key: v.alert_key,
-                blockchain: v.blockchain,
-                protocol: v.protocol,
-                method: v.method,
-                name: v.name,
-                description: v.description,
-                fields: v.fields,
-            })
+            Ok(v)
         })
-        .collect()
+        .collect::<StdResult<Vec<AlertTemplate>>>()?;
+    let next_cursor = split_page(&mut templates, limit, |template| template.template_id);
+
+    Ok((templates, next_cursor))
+}
+
+// Most alerts read for one page of alerts filtered by template version
+const MAX_SCANNED_ALERTS: usize = 100;
+
+/**
+ * Retrieve a page of alerts created from a template. With `before_version`, only alerts that
+ * were created from, or last brought up to, an older version of the template are returned.
+ *
+ * Filtering reads at most MAX_SCANNED_ALERTS alerts per page, so a page can hold fewer alerts
+ * than the limit, or none, and still return a cursor. Only a missing cursor ends the listing.
+ */
+pub fn read_alerts_by_template<S: Storage>(
+    storage: &S,
+    template_id: u64,
+    before_version: Option<u64>,
+    start_after: Option<String>, // alert_key of the last alert on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Alert, String>> {
+    let limit = calc_limit(limit);
+    let (start, end, order) = calc_range(start_after.as_ref().map(|k| k.as_bytes()), order);
+
+    let index_bucket: ReadonlyBucket<S, bool> = ReadonlyBucket::multilevel(
+        &[PREFIX_TEMPLATE_ALERT, &template_id.to_be_bytes()],
+        storage,
+    );
+    let alerts_bucket: ReadonlyBucket<S, Alert> = ReadonlyBucket::new(PREFIX_ALERT, storage);
+    let scan_limit: usize = match before_version {
+        Some(_) => MAX_SCANNED_ALERTS.max(limit + 1),
+        None => limit + 1,
+    };
+    let scanned: Vec<Alert> = index_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(scan_limit)
+        .map(|item| {
+            let (k, _v) = item?;
+            alerts_bucket.load(&k)
+        })
+        .collect::<StdResult<Vec<Alert>>>()?;
+    // Once the scan runs out, the next page continues after the last alert read
+    let last_scanned: Option<String> = match scanned.len() {
+        len if len == scan_limit => scanned.last().map(|alert| alert.alert_key.clone()),
+        _ => None,
+    };
+    let mut alerts: Vec<Alert> = scanned
+        .into_iter()
+        .filter(|alert| match before_version {
+            Some(version) => alert
+                .template
+                .as_ref()
+                .map(|template| template.version < version)
+                .unwrap_or(false),
+            None => true,
+        })
+        .take(limit + 1)
+        .collect();
+    let next_cursor = split_page(&mut alerts, limit, |alert| alert.alert_key.clone());
+
+    Ok((alerts, next_cursor.or(last_scanned)))
+}
+
+pub fn count_alerts_for_template<S: Storage>(storage: &S, template_id: u64) -> StdResult<u64> {
+    read_count(storage, PREFIX_TEMPLATE_COUNT, &template_id.to_be_bytes())
 }
 
 static PREFIX_SUBSCRIPTION: &[u8] = b"subscription";
+// Secondary indexes of subscriptions, stored under `PREFIX_ALERT_SUBSCRIPTION/<alert_key>/<id>`
+// with the subscriber as value, and `PREFIX_ADDRESS_ALERT/<subscriber>/<alert_key>/<id>` with
+// empty values. The subscriptions themselves live under `PREFIX_SUBSCRIPTION/<subscriber>/<id>`.
+static PREFIX_ALERT_SUBSCRIPTION: &[u8] = b"alert_subscription";
+static PREFIX_ADDRESS_ALERT: &[u8] = b"address_alert";
+// Number of subscriptions per subscriber, and per alert. Since an address can subscribe to an
+// alert more than once, the distinct subscribers of an alert are counted separately.
+static PREFIX_ADDRESS_SUBSCRIPTION_COUNT: &[u8] = b"address_subscription_count";
+static PREFIX_ALERT_SUBSCRIPTION_COUNT: &[u8] = b"alert_subscription_count";
+static PREFIX_ALERT_SUBSCRIBER_COUNT: &[u8] = b"alert_subscriber_count";
+// Last subscription id handed out, ids start at 1
+static PREFIX_SUBSCRIPTION_ID: &[u8] = b"subscription_id";
+
+pub fn next_subscription_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    next_id(storage, PREFIX_SUBSCRIPTION_ID)
+}
+
 pub fn store_subscription_for_address<S: Storage>(
     storage: &mut S,
     subscriber_addr: CanonicalAddr,
     subscription: Subscription,
 ) -> StdResult<()> {
+    let id: [u8; 8] = subscription.subscription_id.to_be_bytes();
+    let is_new: bool =
+        may_read_subscription_for_address(storage, &subscriber_addr, subscription.subscription_id)?
+            .is_none();
+    let is_new_subscriber: bool =
+        !has_subscription_to_alert(storage, &subscriber_addr, &subscription.alert_key);
+
+    let mut alert_bucket: Bucket<S, CanonicalAddr> = Bucket::multilevel(
+        &[PREFIX_ALERT_SUBSCRIPTION, subscription.alert_key.as_bytes()],
+        storage,
+    );
+    alert_bucket.save(&id, &subscriber_addr)?;
+
+    let mut address_bucket: Bucket<S, bool> = Bucket::multilevel(
+        &[
+            PREFIX_ADDRESS_ALERT,
+            subscriber_addr.as_slice(),
+            subscription.alert_key.as_bytes(),
+        ],
+        storage,
+    );
+    address_bucket.save(&id, &true)?;
+
     let mut bucket: Bucket<S, Subscription> =
         Bucket::multilevel(&[PREFIX_SUBSCRIPTION, subscriber_addr.as_slice()], storage);
-    bucket.save(subscription.alert_key.as_bytes(), &subscription)
+    bucket.save(&id, &subscription)?;
+
+    if is_new {
+        update_subscription_counts(
+            storage,
+            &subscriber_addr,
+            &subscription.alert_key,
+            is_new_subscriber,
+            true,
+        )?;
+    }
+    Ok(())
 }
 
 pub fn remove_subscription_for_address<S: Storage>(
     storage: &mut S,
     subscriber_address: CanonicalAddr,
-    alert_key: String,
-) {
+    subscription_id: u64,
+) -> StdResult<()> {
+    let subscription: Subscription =
+        read_subscription_for_address(storage, &subscriber_address, subscription_id)?;
+    let id: [u8; 8] = subscription_id.to_be_bytes();
+
+    let mut alert_bucket: Bucket<S, CanonicalAddr> = Bucket::multilevel(
+        &[PREFIX_ALERT_SUBSCRIPTION, subscription.alert_key.as_bytes()],
+        storage,
+    );
+    alert_bucket.remove(&id);
+
+    let mut address_bucket: Bucket<S, bool> = Bucket::multilevel(
+        &[
+            PREFIX_ADDRESS_ALERT,
+            subscriber_address.as_slice(),
+            subscription.alert_key.as_bytes(),
+        ],
+        storage,
+    );
+    address_bucket.remove(&id);
+
     let mut bucket: Bucket<S, Subscription> = Bucket::multilevel(
         &[PREFIX_SUBSCRIPTION, subscriber_address.as_slice()],
         storage,
     );
-    bucket.remove(alert_key.as_bytes())
+    bucket.remove(&id);
+
+    let was_last: bool =
+        !has_subscription_to_alert(storage, &subscriber_address, &subscription.alert_key);
+    update_subscription_counts(
+        storage,
+        &subscriber_address,
+        &subscription.alert_key,
+        was_last,
+        false,
+    )
 }
 
-pub fn read_subscription_for_address<S: Storage>(
+// Whether the address has any subscription to the alert, according to the address index
+fn has_subscription_to_alert<S: Storage>(
     storage: &S,
     subscriber_addr: &CanonicalAddr,
     alert_key: &String,
+) -> bool {
+    let address_bucket: ReadonlyBucket<S, bool> = ReadonlyBucket::multilevel(
+        &[
+            PREFIX_ADDRESS_ALERT,
+            subscriber_addr.as_slice(),
+            alert_key.as_bytes(),
+        ],
+        storage,
+    );
+    let has_subscription: bool = address_bucket
+        .range(None, None, Order::Ascending)
+        .next()
+        .is_some();
+    has_subscription
+}
+
+// Counts a subscription in, or out of, the totals and the counts of its subscriber and alert
+fn update_subscription_counts<S: Storage>(
+    storage: &mut S,
+    subscriber_addr: &CanonicalAddr,
+    alert_key: &String,
+    distinct_subscriber: bool,
+    add: bool,
+) -> StdResult<()> {
+    update_count(
+        Bucket::new(PREFIX_ADDRESS_SUBSCRIPTION_COUNT, storage),
+        subscriber_addr.as_slice(),
+        add,
+    )?;
+    update_count(
+        Bucket::new(PREFIX_ALERT_SUBSCRIPTION_COUNT, storage),
+        alert_key.as_bytes(),
+        add,
+    )?;
+    if distinct_subscriber {
+        update_count(
+            Bucket::new(PREFIX_ALERT_SUBSCRIBER_COUNT, storage),
+            alert_key.as_bytes(),
+            add,
+        )?;
+    }
+    update_count(Bucket::new(PREFIX_TOTAL, storage), TOTAL_SUBSCRIPTIONS, add)
+}
+
+// Removes up to limit subscriptions to an alert, oldest first, returns them with their subscribers
+pub fn remove_subscriptions_for_alert<S: Storage>(
+    storage: &mut S,
+    alert_key: &String,
+    limit: usize,
+) -> StdResult<Vec<(CanonicalAddr, Subscription)>> {
+    let subscriptions: Vec<(u64, CanonicalAddr)> = {
+        let alert_bucket: ReadonlyBucket<S, CanonicalAddr> =
+            ReadonlyBucket::multilevel(&[PREFIX_ALERT_SUBSCRIPTION, alert_key.as_bytes()], storage);
+        alert_bucket
+            .range(None, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (k, subscriber_addr) = item?;
+                Ok((parse_subscription_id(&k)?, subscriber_addr))
+            })
+            .collect::<StdResult<Vec<(u64, CanonicalAddr)>>>()?
+    };
+
+    let mut removed: Vec<(CanonicalAddr, Subscription)> = vec![];
+    for (subscription_id, subscriber_addr) in subscriptions {
+        let subscription: Subscription =
+            read_subscription_for_address(storage, &subscriber_addr, subscription_id)?;
+        remove_subscription_for_address(storage, subscriber_addr.clone(), subscription_id)?;
+        removed.push((subscriber_addr, subscription));
+    }
+    Ok(removed)
+}
+
+// Number of subscriptions of an address, expired ones included
+pub fn count_subscriptions_for_address<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+) -> StdResult<u64> {
+    read_count(
+        storage,
+        PREFIX_ADDRESS_SUBSCRIPTION_COUNT,
+        subscriber_addr.as_slice(),
+    )
+}
+
+// Number of subscriptions to an alert and of distinct addresses holding them, expired included
+pub fn count_subscriptions_for_alert<S: Storage>(
+    storage: &S,
+    alert_key: &String,
+) -> StdResult<(u64, u64)> {
+    Ok((
+        read_count(
+            storage,
+            PREFIX_ALERT_SUBSCRIPTION_COUNT,
+            alert_key.as_bytes(),
+        )?,
+        read_count(storage, PREFIX_ALERT_SUBSCRIBER_COUNT, alert_key.as_bytes())?,
+    ))
+}
+
+// Total number of alerts and subscriptions
+pub fn read_totals<S: Storage>(storage: &S) -> StdResult<(u64, u64)> {
+    Ok((
+        read_count(storage, PREFIX_TOTAL, TOTAL_ALERTS)?,
+        read_count(storage, PREFIX_TOTAL, TOTAL_SUBSCRIPTIONS)?,
+    ))
+}
+
+fn read_count<S: Storage>(storage: &S, prefix: &[u8], key: &[u8]) -> StdResult<u64> {
+    let bucket: ReadonlyBucket<S, u64> = ReadonlyBucket::new(prefix, storage);
+    Ok(bucket.may_load(key)?.unwrap_or(0))
+}
+
+// Most subscriptions read for one page of subscribers
+const MAX_SCANNED_SUBSCRIPTIONS: usize = 100;
+
+/**
+ * Retrieve a page of subscriptions to an alert together with their subscribers, returns the
+ * subscription id to start the next page after. Subscriptions that expired by `time` are skipped.
+ *
+ * At most MAX_SCANNED_SUBSCRIPTIONS subscriptions are read per page, so a page can hold fewer
+ * subscribers than the limit, or none, and still return a cursor. Only a missing cursor ends the
+ * listing.
+ */
+pub fn read_subscribers_for_alert<S: Storage>(
+    storage: &S,
+    alert_key: &String,
+    time: u64,
+    start_after: Option<u64>, // subscription id of the last subscription on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<(CanonicalAddr, Subscription), u64>> {
+    let limit = calc_limit(limit);
+    let start_after: Option<[u8; 8]> = start_after.map(|id| id.to_be_bytes());
+    let (start, end, order) = calc_range(start_after.as_ref().map(|id| &id[..]), order);
+
+    let alert_bucket: ReadonlyBucket<S, CanonicalAddr> =
+        ReadonlyBucket::multilevel(&[PREFIX_ALERT_SUBSCRIPTION, alert_key.as_bytes()], storage);
+    let scan_limit: usize = MAX_SCANNED_SUBSCRIPTIONS.max(limit + 1);
+    let scanned: Vec<(CanonicalAddr, Subscription)> = alert_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(scan_limit)
+        .map(|item| {
+            let (k, subscriber_addr) = item?;
+            let subscription: Subscription = read_subscription_for_address(
+                storage,
+                &subscriber_addr,
+                parse_subscription_id(&k)?,
+            )?;
+            Ok((subscriber_addr, subscription))
+        })
+        .collect::<StdResult<Vec<(CanonicalAddr, Subscription)>>>()?;
+    // Once the scan runs out, the next page continues after the last subscription read
+    let last_scanned: Option<u64> = match scanned.len() {
+        len if len == scan_limit => scanned
+            .last()
+            .map(|(_, subscription)| subscription.subscription_id),
+        _ => None,
+    };
+    let mut subscribers: Vec<(CanonicalAddr, Subscription)> = scanned
+        .into_iter()
+        .filter(|(_, subscription)| !subscription.is_expired(time))
+        .take(limit + 1)
+        .collect();
+    let next_cursor = split_page(&mut subscribers, limit, |(_, subscription)| {
+        subscription.subscription_id
+    });
+
+    Ok((subscribers, next_cursor.or(last_scanned)))
+}
+
+pub fn read_subscription_for_address<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+    subscription_id: u64,
 ) -> StdResult<Subscription> {
     let bucket: ReadonlyBucket<S, Subscription> =
-        ReadonlyBucket::multilevel(&[PREFIX_SUBSCRIPTION, subscriber_addr.as_slice()], &storage);
-    bucket.load(alert_key.as_bytes())
+        ReadonlyBucket::multilevel(&[PREFIX_SUBSCRIPTION, subscriber_addr.as_slice()], storage);
+    bucket.load(&subscription_id.to_be_bytes())
 }
 
+pub fn may_read_subscription_for_address<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+    subscription_id: u64,
+) -> StdResult<Option<Subscription>> {
+    let bucket: ReadonlyBucket<S, Subscription> =
+        ReadonlyBucket::multilevel(&[PREFIX_SUBSCRIPTION, subscriber_addr.as_slice()], storage);
+    bucket.may_load(&subscription_id.to_be_bytes())
+}
+
+// Retrieve all subscriptions of an address to one alert, oldest first
+pub fn read_subscriptions_for_address_and_alert<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+    alert_key: &String,
+) -> StdResult<Vec<Subscription>> {
+    let address_bucket: ReadonlyBucket<S, bool> = ReadonlyBucket::multilevel(
+        &[
+            PREFIX_ADDRESS_ALERT,
+            subscriber_addr.as_slice(),
+            alert_key.as_bytes(),
+        ],
+        storage,
+    );
+    address_bucket
+        .range(None, None, Order::Ascending)
+        .map(|item| {
+            let (k, _v) = item?;
+            read_subscription_for_address(storage, subscriber_addr, parse_subscription_id(&k)?)
+        })
+        .collect()
+}
+
+// Retrieve a page of subscriptions of an address, returns the subscription id to start the
+// next page after
 pub fn read_subscriptions_for_address<S: Storage>(
     storage: &S,
     subscriber_address: CanonicalAddr,
-    start_after: Option<CanonicalAddr>, // Kinda like a cursor for pagination
+    start_after: Option<u64>, // subscription id of the last subscription on the previous page
     limit: Option<u32>,
     order: Option<OrderBy>,
-) -> StdResult<Vec<Subscription>> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let (start, end, order) = match order {
-        Some(OrderBy::Asc) => (calc_range_start(start_after), None, OrderBy::Asc),
-        _ => (None, calc_range_end(start_after), OrderBy::Desc),
-    };
+) -> StdResult<Page<Subscription, u64>> {
+    let limit = calc_limit(limit);
+    let start_after: Option<[u8; 8]> = start_after.map(|id| id.to_be_bytes());
+    let (start, end, order) = calc_range(start_after.as_ref().map(|id| &id[..]), order);
 
     let subscriptions_bucket: ReadonlyBucket<S, Subscription> = ReadonlyBucket::multilevel(
         &[PREFIX_SUBSCRIPTION, subscriber_address.as_slice()],
-        &storage,
+        storage,
     );
-    subscriptions_bucket
-        .range(start.as_deref(), end.as_deref(), order.into())
-        .take(limit)
+    let mut subscriptions: Vec<Subscription> = subscriptions_bucket
+        .range(start.as_deref(), end.as_deref(), order)
+        .take(limit + 1)
         .map(|item| {
             let (_k, v) = item?;
-            Ok(Subscription {
-                alert_key: v.alert_key,
-                field_values: v.field_values,
-            })
+            Ok(v)
+        })
+        .collect::<StdResult<Vec<Subscription>>>()?;
+    let next_cursor = split_page(&mut subscriptions, limit, |subscription| {
+        subscription.subscription_id
+    });
+
+    Ok((subscriptions, next_cursor))
+}
+
+// Subscription ids are stored big-endian, so they sort in the order they were handed out
+fn parse_subscription_id(key: &[u8]) -> StdResult<u64> {
+    let mut id: [u8; 8] = [0; 8];
+    if key.len() != id.len() {
+        return Err(StdError::generic_err(
+            "Invalid subscription id in storage key",
+        ));
+    }
+    id.copy_from_slice(key);
+    Ok(u64::from_be_bytes(id))
+}
+
+// Delivery channels of an address, stored under `PREFIX_CHANNEL/<address>/<name>`
+static PREFIX_CHANNEL: &[u8] = b"channel";
+
+pub fn store_channel<S: Storage>(
+    storage: &mut S,
+    addr: &CanonicalAddr,
+    channel: &Channel,
+) -> StdResult<()> {
+    let mut bucket: Bucket<S, Channel> =
+        Bucket::multilevel(&[PREFIX_CHANNEL, addr.as_slice()], storage);
+    bucket.save(channel.name.as_bytes(), channel)
+}
+
+pub fn remove_channel<S: Storage>(storage: &mut S, addr: &CanonicalAddr, name: &String) {
+    let mut bucket: Bucket<S, Channel> =
+        Bucket::multilevel(&[PREFIX_CHANNEL, addr.as_slice()], storage);
+    bucket.remove(name.as_bytes())
+}
+
+pub fn may_read_channel<S: Storage>(
+    storage: &S,
+    addr: &CanonicalAddr,
+    name: &String,
+) -> StdResult<Option<Channel>> {
+    let bucket: ReadonlyBucket<S, Channel> =
+        ReadonlyBucket::multilevel(&[PREFIX_CHANNEL, addr.as_slice()], storage);
+    bucket.may_load(name.as_bytes())
+}
+
+// Retrieve all channels of an address, there are at most a handful of them
+pub fn read_channels<S: Storage>(storage: &S, addr: &CanonicalAddr) -> StdResult<Vec<Channel>> {
+    let bucket: ReadonlyBucket<S, Channel> =
+        ReadonlyBucket::multilevel(&[PREFIX_CHANNEL, addr.as_slice()], storage);
+    bucket
+        .range(None, None, Order::Ascending)
+        .map(|item| {
+            let (_k, v) = item?;
+            Ok(v)
         })
         .collect()
 }
 
-// this will set the first key after the provided key, by appending a 1 byte
-fn calc_range_start(start_after: Option<CanonicalAddr>) -> Option<Vec<u8>> {
-    start_after.map(|idx| {
-        let mut v = idx.as_slice().to_vec();
-        v.push(1);
+// Notifications delivered to a subscriber, stored under `PREFIX_NOTIFICATION/<subscriber>/<id>`
+static PREFIX_NOTIFICATION: &[u8] = b"notification";
+// Secondary index of unread notifications, stored under `PREFIX_UNREAD_NOTIFICATION/<subscriber>/<id>`
+// with empty values
+static PREFIX_UNREAD_NOTIFICATION: &[u8] = b"unread_notification";
+// Number of notifications kept for each subscriber
+static PREFIX_NOTIFICATION_COUNT: &[u8] = b"notification_count";
+// Last notification id handed out, ids start at 1
+static PREFIX_NOTIFICATION_ID: &[u8] = b"notification_id";
+
+// Notifications of a subscriber beyond this are pruned, oldest first
+pub const MAX_NOTIFICATIONS: u64 = 100;
+
+pub fn next_notification_id<S: Storage>(storage: &mut S) -> StdResult<u64> {
+    next_id(storage, PREFIX_NOTIFICATION_ID)
+}
+
+// Saves a new or updated notification, pruning the oldest ones once there are too many
+pub fn store_notification<S: Storage>(
+    storage: &mut S,
+    subscriber_addr: &CanonicalAddr,
+    notification: &Notification,
+) -> StdResult<()> {
+    let id: [u8; 8] = notification.notification_id.to_be_bytes();
+
+    let mut unread_bucket: Bucket<S, bool> = Bucket::multilevel(
+        &[PREFIX_UNREAD_NOTIFICATION, subscriber_addr.as_slice()],
+        storage,
+    );
+    if notification.read {
+        unread_bucket.remove(&id);
+    } else {
+        unread_bucket.save(&id, &true)?;
+    }
+
+    let mut bucket: Bucket<S, Notification> =
+        Bucket::multilevel(&[PREFIX_NOTIFICATION, subscriber_addr.as_slice()], storage);
+    let is_new: bool = bucket.may_load(&id)?.is_none();
+    bucket.save(&id, notification)?;
+    if !is_new {
+        return Ok(());
+    }
+
+    let count_bucket: ReadonlyBucket<S, u64> =
+        ReadonlyBucket::new(PREFIX_NOTIFICATION_COUNT, storage);
+    let mut count: u64 = count_bucket
+        .may_load(subscriber_addr.as_slice())?
+        .unwrap_or(0)
+        + 1;
+    if count > MAX_NOTIFICATIONS {
+        remove_oldest_notification(storage, subscriber_addr)?;
+        count -= 1;
+    }
+    let mut count_bucket: Bucket<S, u64> = Bucket::new(PREFIX_NOTIFICATION_COUNT, storage);
+    count_bucket.save(subscriber_addr.as_slice(), &count)
+}
+
+fn remove_oldest_notification<S: Storage>(
+    storage: &mut S,
+    subscriber_addr: &CanonicalAddr,
+) -> StdResult<()> {
+    let mut bucket: Bucket<S, Notification> =
+        Bucket::multilevel(&[PREFIX_NOTIFICATION, subscriber_addr.as_slice()], storage);
+    let oldest: Option<Vec<u8>> = match bucket.range(None, None, Order::Ascending).next() {
+        Some(item) => Some(item?.0),
+        None => None,
+    };
+    if let Some(id) = oldest {
+        bucket.remove(&id);
+        let mut unread_bucket: Bucket<S, bool> = Bucket::multilevel(
+            &[PREFIX_UNREAD_NOTIFICATION, subscriber_addr.as_slice()],
+            storage,
+        );
+        unread_bucket.remove(&id);
+    }
+    Ok(())
+}
+
+pub fn may_read_notification<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+    notification_id: u64,
+) -> StdResult<Option<Notification>> {
+    let bucket: ReadonlyBucket<S, Notification> =
+        ReadonlyBucket::multilevel(&[PREFIX_NOTIFICATION, subscriber_addr.as_slice()], storage);
+    bucket.may_load(&notification_id.to_be_bytes())
+}
+
+// Retrieve a page of notifications of a subscriber, returns the notification id to start the
+// next page after
+pub fn read_notifications<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+    unread_only: bool,
+    start_after: Option<u64>, // notification id of the last notification on the previous page
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Page<Notification, u64>> {
+    let limit = calc_limit(limit);
+    let start_after: Option<[u8; 8]> = start_after.map(|id| id.to_be_bytes());
+    let (start, end, order) = calc_range(start_after.as_ref().map(|id| &id[..]), order);
+
+    let bucket: ReadonlyBucket<S, Notification> =
+        ReadonlyBucket::multilevel(&[PREFIX_NOTIFICATION, subscriber_addr.as_slice()], storage);
+    let mut notifications: Vec<Notification> = if unread_only {
+        let unread_bucket: ReadonlyBucket<S, bool> = ReadonlyBucket::multilevel(
+            &[PREFIX_UNREAD_NOTIFICATION, subscriber_addr.as_slice()],
+            storage,
+        );
+        unread_bucket
+            .range(start.as_deref(), end.as_deref(), order)
+            .take(limit + 1)
+            .map(|item| {
+                let (k, _v) = item?;
+                bucket.load(&k)
+            })
+            .collect::<StdResult<Vec<Notification>>>()?
+    } else {
+        bucket
+            .range(start.as_deref(), end.as_deref(), order)
+            .take(limit + 1)
+            .map(|item| {
+                let (_k, v) = item?;
+                Ok(v)
+            })
+            .collect::<StdResult<Vec<Notification>>>()?
+    };
+    let next_cursor = split_page(&mut notifications, limit, |notification| {
+        notification.notification_id
+    });
+
+    Ok((notifications, next_cursor))
+}
+
+// At most MAX_NOTIFICATIONS, so counting the index is cheap enough
+pub fn count_unread_notifications<S: Storage>(
+    storage: &S,
+    subscriber_addr: &CanonicalAddr,
+) -> StdResult<u64> {
+    let unread_bucket: ReadonlyBucket<S, bool> = ReadonlyBucket::multilevel(
+        &[PREFIX_UNREAD_NOTIFICATION, subscriber_addr.as_slice()],
+        storage,
+    );
+    Ok(unread_bucket.range(None, None, Order::Ascending).count() as u64)
+}
+
+// Version of the storage layout. Contracts that never stored one use version 1, the layout of
+// the first release.
+static PREFIX_CONTRACT_VERSION: &[u8] = b"contract_version";
+pub const CONTRACT_VERSION: u64 = 2;
+
+pub fn store_contract_version<S: Storage>(storage: &mut S, version: u64) -> StdResult<()> {
+    singleton(storage, PREFIX_CONTRACT_VERSION).save(&version)
+}
+
+pub fn read_contract_version<S: Storage>(storage: &S) -> StdResult<u64> {
+    Ok(singleton_read(storage, PREFIX_CONTRACT_VERSION)
+        .may_load()?
+        .unwrap_or(1))
+}
+
+// Index of version 1, stored under `PREFIX_ALERT_SUBSCRIBER/<alert_key>/<subscriber>`
+static PREFIX_ALERT_SUBSCRIBER: &[u8] = b"alert_subscriber";
+
+// Progress of a migration spread over several MigrateMsg, only stored while one is running
+static PREFIX_MIGRATION: &[u8] = b"migration";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStep {
+    // Dropping the indexes and counters
+    ClearIndexes,
+    // Saving again and indexing the alerts after the cursor
+    Alerts { start_after: Option<Vec<u8>> },
+    // Moving and indexing the subscriptions after the cursor, a raw key of PREFIX_SUBSCRIPTION
+    Subscriptions { start_after: Option<Vec<u8>> },
+}
+
+pub fn read_migration_step<S: Storage>(storage: &S) -> StdResult<Option<MigrationStep>> {
+    singleton_read(storage, PREFIX_MIGRATION).may_load()
+}
+
+/**
+ * Migrates the storage layout of version 1 to version 2.
+ *
+ * Version 1 kept at most one subscription per address and alert, stored under
+ * `PREFIX_SUBSCRIPTION/<subscriber>/<alert_key>` without an id. Every such subscription gets an
+ * id and moves to `PREFIX_SUBSCRIPTION/<subscriber>/<id>`. Alerts and subscriptions are saved
+ * again in the current schema, and all indexes and counters are dropped and rebuilt from them,
+ * so contracts with a partially indexed layout end up consistent as well.
+ *
+ * Version 1 did not canonicalize keys. The rebuilt indexes fold blockchains, protocols and alert
+ * keys to lower case, so its alerts are found in any case.
+ *
+ * At most `limit` records are removed or rewritten per call, so large stores can be migrated
+ * within the gas limit. The progress is kept under PREFIX_MIGRATION and the next call continues
+ * from there. Returns whether the migration finished.
+ */
+pub fn migrate_v1_to_v2<S: Storage>(storage: &mut S, limit: usize) -> StdResult<bool> {
+    let mut step: MigrationStep =
+        read_migration_step(storage)?.unwrap_or(MigrationStep::ClearIndexes);
+    let mut budget: usize = limit;
+    while budget > 0 {
+        step = match step {
+            MigrationStep::ClearIndexes => {
+                for prefix in &[
+                    PREFIX_ALERT_SUBSCRIBER,
+                    PREFIX_FOLDED_KEY,
+                    PREFIX_BLOCKCHAIN_ALERT,
+                    PREFIX_PROTOCOL_ALERT,
+                    PREFIX_BLOCKCHAIN_COUNT,
+                    PREFIX_PROTOCOL_COUNT,
+                    PREFIX_TEMPLATE_ALERT,
+                    PREFIX_TEMPLATE_COUNT,
+                    PREFIX_TAG_ALERT,
+                    PREFIX_CATEGORY_ALERT,
+                    PREFIX_SEVERITY_ALERT,
+                    PREFIX_TOTAL,
+                    PREFIX_ALERT_SUBSCRIPTION,
+                    PREFIX_ADDRESS_ALERT,
+                    PREFIX_ADDRESS_SUBSCRIPTION_COUNT,
+                    PREFIX_ALERT_SUBSCRIPTION_COUNT,
+                    PREFIX_ALERT_SUBSCRIBER_COUNT,
+                ] {
+                    budget -= remove_prefix(storage, prefix, budget);
+                }
+                if budget == 0 {
+                    MigrationStep::ClearIndexes
+                } else {
+                    let config: Config = read_config(storage)?;
+                    store_config(storage, &config)?;
+                    MigrationStep::Alerts { start_after: None }
+                }
+            }
+            MigrationStep::Alerts { start_after } => {
+                let alerts: Vec<(Vec<u8>, Alert)> = {
+                    let bucket: ReadonlyBucket<S, Alert> =
+                        ReadonlyBucket::new(PREFIX_ALERT, storage);
+                    let start: Option<Vec<u8>> = calc_range_start(start_after.as_deref());
+                    bucket
+                        .range(start.as_deref(), None, Order::Ascending)
+                        .take(budget)
+                        .collect::<StdResult<Vec<(Vec<u8>, Alert)>>>()?
+                };
+                let finished: bool = alerts.len() < budget;
+                budget -= alerts.len();
+                let start_after: Option<Vec<u8>> = alerts.last().map(|(key, _)| key.clone());
+                for (key, alert) in alerts {
+                    update_alert_indexes(storage, &alert, true)?;
+                    let mut bucket: Bucket<S, Alert> = Bucket::new(PREFIX_ALERT, storage);
+                    bucket.save(&key, &alert)?;
+                }
+                if finished {
+                    MigrationStep::Subscriptions { start_after: None }
+                } else {
+                    MigrationStep::Alerts { start_after }
+                }
+            }
+            MigrationStep::Subscriptions { start_after } => {
+                let subscriptions: Vec<(Vec<u8>, Subscription)> = {
+                    let bucket: ReadonlyBucket<S, Subscription> =
+                        ReadonlyBucket::new(PREFIX_SUBSCRIPTION, storage);
+                    let start: Option<Vec<u8>> = calc_range_start(start_after.as_deref());
+                    bucket
+                        .range(start.as_deref(), None, Order::Ascending)
+                        .take(budget)
+                        .collect::<StdResult<Vec<(Vec<u8>, Subscription)>>>()?
+                };
+                if subscriptions.len() < budget {
+                    migrate_subscriptions(storage, subscriptions)?;
+                    storage.remove(&to_length_prefixed(PREFIX_MIGRATION));
+                    return Ok(true);
+                }
+                budget -= subscriptions.len();
+                let start_after: Option<Vec<u8>> = subscriptions.last().map(|(key, _)| key.clone());
+                migrate_subscriptions(storage, subscriptions)?;
+                MigrationStep::Subscriptions { start_after }
+            }
+        };
+    }
+    singleton(storage, PREFIX_MIGRATION).save(&step)?;
+    Ok(false)
+}
+
+/**
+ * Keys are the length prefixed subscriber followed by the alert key, or the id if the
+ * subscription already has one.
+ *
+ * The moved subscriptions are stored under an 8 byte id starting with a 0 byte, which sorts
+ * before every alert key of the same subscriber. So they stay behind the cursor and are not
+ * visited again.
+ */
+fn migrate_subscriptions<S: Storage>(
+    storage: &mut S,
+    subscriptions: Vec<(Vec<u8>, Subscription)>,
+) -> StdResult<()> {
+    for (key, mut subscription) in subscriptions {
+        let (subscriber_addr, subscription_key) = split_length_prefixed(&key)?;
+        let mut bucket: Bucket<S, Subscription> =
+            Bucket::multilevel(&[PREFIX_SUBSCRIPTION, subscriber_addr.as_slice()], storage);
+        bucket.remove(subscription_key);
+
+        // Ids start at 1, so 0 is the default of subscriptions stored without one
+        if subscription.subscription_id == 0 {
+            subscription.subscription_id = next_subscription_id(storage)?;
+        }
+        store_subscription_for_address(storage, subscriber_addr, subscription)?;
+    }
+    Ok(())
+}
+
+// Removes up to limit records stored under a namespace, whatever the type of their values.
+// Returns the number of records removed.
+fn remove_prefix<S: Storage>(storage: &mut S, namespace: &[u8], limit: usize) -> usize {
+    let start: Vec<u8> = to_length_prefixed(namespace);
+    // Namespaces are ASCII, so the last byte can't overflow
+    let mut end: Vec<u8> = start.clone();
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+    let keys: Vec<Vec<u8>> = storage
+        .range(Some(&start), Some(&end), Order::Ascending)
+        .take(limit)
+        .map(|(key, _value)| key)
+        .collect();
+    for key in &keys {
+        storage.remove(key);
+    }
+    keys.len()
+}
+
+// Splits a key of a multilevel bucket into its first, length prefixed, level and the rest
+fn split_length_prefixed(key: &[u8]) -> StdResult<(CanonicalAddr, &[u8])> {
+    if key.len() < 2 {
+        return Err(StdError::generic_err("Invalid length prefixed storage key"));
+    }
+    let len: usize = (usize::from(key[0]) << 8) | usize::from(key[1]);
+    if key.len() < 2 + len {
+        return Err(StdError::generic_err("Invalid length prefixed storage key"));
+    }
+    Ok((CanonicalAddr::from(&key[2..2 + len]), &key[2 + len..]))
+}
+
+// Increments and returns the counter stored under `key`
+fn next_id<S: Storage>(storage: &mut S, key: &[u8]) -> StdResult<u64> {
+    let id: u64 = singleton_read(storage, key).may_load()?.unwrap_or(0) + 1;
+    singleton(storage, key).save(&id)?;
+    Ok(id)
+}
+
+fn calc_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+// Returns the range bounds to read the keys after `start_after` in the given order (Desc by default)
+fn calc_range(
+    start_after: Option<&[u8]>,
+    order: Option<OrderBy>,
+) -> (Option<Vec<u8>>, Option<Vec<u8>>, Order) {
+    match order {
+        Some(OrderBy::Asc) => (calc_range_start(start_after), None, Order::Ascending),
+        _ => (None, calc_range_end(start_after), Order::Descending),
+    }
+}
+
+// this will set the first key after the provided key, by appending a 0 byte
+fn calc_range_start(start_after: Option<&[u8]>) -> Option<Vec<u8>> {
+    start_after.map(|key| {
+        let mut v = key.to_vec();
+        v.push(0);
         v
     })
 }
 
-// this will set the first key after the provided key in Desc
-fn calc_range_end(start_after: Option<CanonicalAddr>) -> Option<Vec<u8>> {
-    start_after.map(|idx| idx.as_slice().to_vec())
+// this will set the first key after the provided key in Desc, as range ends are exclusive
+fn calc_range_end(start_after: Option<&[u8]>) -> Option<Vec<u8>> {
+    start_after.map(|key| key.to_vec())
+}
+
+// Pages are read with one extra item, so we know whether there is a next page.
+// Drops that item and returns the cursor of the last item on the page if there is a next page.
+fn split_page<T, C, F: Fn(&T) -> C>(items: &mut Vec<T>, limit: usize, cursor: F) -> Option<C> {
+    if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(cursor)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::ReadonlyStorage;
+    use cosmwasm_storage::to_length_prefixed_nested;
+
+    #[test]
+    fn splits_length_prefixed_keys() {
+        let key: Vec<u8> = [to_length_prefixed(b"alice"), b"terra.anchor.a".to_vec()].concat();
+        let (first, rest) = split_length_prefixed(&key).unwrap();
+        assert_eq!(first.as_slice(), b"alice");
+        assert_eq!(rest, b"terra.anchor.a");
+
+        let key: Vec<u8> = to_length_prefixed(b"alice");
+        let (first, rest) = split_length_prefixed(&key).unwrap();
+        assert_eq!(first.as_slice(), b"alice");
+        assert!(rest.is_empty());
+        assert!(split_length_prefixed(&[0]).is_err());
+        assert!(split_length_prefixed(&[0, 6, b'a']).is_err());
+    }
+
+    #[test]
+    fn removes_prefixes_in_batches() {
+        let mut storage = MockStorage::new();
+        for i in 0..5u8 {
+            storage.set(
+                &[to_length_prefixed_nested(&[b"total", &[i]]), vec![i]].concat(),
+                b"1",
+            );
+        }
+        // Neither a namespace sharing the first bytes nor the one after are touched
+        storage.set(&[to_length_prefixed(b"totals"), vec![0]].concat(), b"1");
+        storage.set(&[to_length_prefixed(b"totam"), vec![0]].concat(), b"1");
+
+        assert_eq!(remove_prefix(&mut storage, b"total", 3), 3);
+        assert_eq!(remove_prefix(&mut storage, b"total", 3), 2);
+        assert_eq!(remove_prefix(&mut storage, b"total", 3), 0);
+        assert_eq!(storage.range(None, None, Order::Ascending).count(), 2);
+    }
 }
@@ -0,0 +1,1151 @@
+use crate::error::ContractError;
+use crate::keys::MAX_COMPONENT_LEN;
+use crate::models::{
+    AlertField, AlertFieldType, AlertPrice, Channel, KeyScheme, SubscriptionFieldValue,
+    SubscriptionLimits,
+};
+use cosmwasm_std::{Api, HumanAddr};
+
+// Limits to keep compiled programs (and therefore gas usage) small
+const MAX_PATTERN_LEN: usize = 256;
+const MAX_REPEAT: u32 = 100;
+const MAX_PROGRAM_LEN: usize = 1000;
+// Nodes that compile to nothing, like `(()())`, don't grow the program, so the number of compiled
+// nodes is limited as well to bound the work of nested repetitions
+const MAX_COMPILE_STEPS: usize = 10 * MAX_PROGRAM_LEN;
+
+// Same precision as cosmwasm_std::Decimal
+const DECIMAL_PLACES: usize = 18;
+
+const MAX_CHANNEL_NAME_LEN: usize = 32;
+// Enough for a webhook URL plus the overhead of the encryption scheme
+const MAX_CIPHERTEXT_LEN: usize = 1024;
+
+// Ten years in seconds, keeps expiry times far from overflowing when renewals stack up
+const MAX_BILLING_PERIOD: u64 = 10 * 365 * 24 * 60 * 60;
+
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 32;
+
+// Checks that field definitions are usable and that no field key is used twice
+pub fn validate_alert_fields(fields: &[AlertField]) -> Result<(), ContractError> {
+    for (i, field) in fields.iter().enumerate() {
+        if fields[..i].iter().any(|f| f.field_key == field.field_key) {
+            return Err(ContractError::InvalidField {
+                field_key: field.field_key.clone(),
+                reason: "duplicate field key".to_string(),
+            });
+        }
+        validate_alert_field(field)?;
+    }
+    Ok(())
+}
+
+// Checks that a field definition can be used to validate subscriptions
+pub fn validate_alert_field(field: &AlertField) -> Result<(), ContractError> {
+    if let AlertFieldType::Enum { options } = &field.field_type {
+        if options.is_empty() {
+            return Err(ContractError::InvalidField {
+                field_key: field.field_key.clone(),
+                reason: "enum needs at least one option".to_string(),
+            });
+        }
+    }
+    Regex::new(&field.validation_regex)?;
+    Ok(())
+}
+
+// Checks that a subscription price can actually be paid
+pub fn validate_alert_price(price: &AlertPrice) -> Result<(), ContractError> {
+    let reason: &str = if !is_denom(&price.denom) {
+        "denom is not a valid native denom"
+    } else if price.amount.is_zero() {
+        "amount must be greater than zero, leave the price out for free alerts"
+    } else if price.billing_period == 0 {
+        "billing period must be at least one second"
+    } else if price.billing_period > MAX_BILLING_PERIOD {
+        "billing period must be at most ten years"
+    } else {
+        return Ok(());
+    };
+    Err(ContractError::InvalidPrice {
+        reason: reason.to_string(),
+    })
+}
+
+pub fn validate_subscription_limits(limits: &SubscriptionLimits) -> Result<(), ContractError> {
+    let reason: &str = if limits.max_subscriptions_per_address == 0 {
+        "at least one subscription per address must be allowed"
+    } else if limits.max_field_values == 0 {
+        "at least one field value per subscription must be allowed"
+    } else if limits.max_value_len == 0 {
+        "values must be allowed to be at least one byte long"
+    } else {
+        match &limits.deposit {
+            Some(deposit) if !is_denom(&deposit.denom) => {
+                "deposit denom is not a valid native denom"
+            }
+            Some(deposit) if deposit.amount.is_zero() => {
+                "deposit must be greater than zero, leave it out to not require one"
+            }
+            _ => return Ok(()),
+        }
+    };
+    Err(ContractError::InvalidLimits {
+        reason: reason.to_string(),
+    })
+}
+
+pub fn validate_key_scheme(scheme: &KeyScheme) -> Result<(), ContractError> {
+    if scheme.max_component_len == 0 || scheme.max_component_len > MAX_COMPONENT_LEN as u64 {
+        return Err(ContractError::InvalidKeyScheme {
+            reason: format!(
+                "max_component_len must be between 1 and {}",
+                MAX_COMPONENT_LEN
+            ),
+        });
+    }
+    Ok(())
+}
+
+// Rejects subscriptions whose values would take up too much storage
+pub fn check_field_value_limits(
+    limits: &SubscriptionLimits,
+    field_values: &[SubscriptionFieldValue],
+) -> Result<(), ContractError> {
+    if field_values.len() as u64 > limits.max_field_values {
+        return Err(ContractError::TooManyFieldValues {
+            max: limits.max_field_values,
+        });
+    }
+    match field_values
+        .iter()
+        .find(|field_value| field_value.value.len() as u64 > limits.max_value_len)
+    {
+        Some(field_value) => Err(ContractError::FieldValueTooLong {
+            field_key: field_value.field_key.clone(),
+            max_len: limits.max_value_len,
+        }),
+        None => Ok(()),
+    }
+}
+
+// Names are short identifiers, the contact handle itself only exists encrypted
+pub fn validate_channel(channel: &Channel) -> Result<(), ContractError> {
+    let reason: String = if channel.name.is_empty() || channel.name.len() > MAX_CHANNEL_NAME_LEN {
+        format!("name must be 1 to {} characters", MAX_CHANNEL_NAME_LEN)
+    } else if !channel
+        .name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        "name may only contain letters, digits, - and _".to_string()
+    } else if channel.ciphertext.is_empty() || channel.ciphertext.len() > MAX_CIPHERTEXT_LEN {
+        format!("ciphertext must be 1 to {} bytes", MAX_CIPHERTEXT_LEN)
+    } else {
+        return Ok(());
+    };
+    Err(ContractError::InvalidChannel {
+        name: channel.name.clone(),
+        reason,
+    })
+}
+
+// Folds tags to lower case and drops duplicates, so every spelling ends up in the same index
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, ContractError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ContractError::InvalidTag {
+            tag: tags[MAX_TAGS].clone(),
+            reason: format!("at most {} tags are allowed", MAX_TAGS),
+        });
+    }
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags {
+        let tag: String = normalize_tag(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+// Also used for categories
+pub fn normalize_tag(tag: &str) -> Result<String, ContractError> {
+    let reason: String = if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+        format!("must be 1 to {} characters", MAX_TAG_LEN)
+    } else if !tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        "may only contain letters, digits, - and _".to_string()
+    } else {
+        return Ok(tag.to_ascii_lowercase());
+    };
+    Err(ContractError::InvalidTag {
+        tag: tag.to_string(),
+        reason,
+    })
+}
+
+/**
+ * Checks the values of a subscription against the fields of its alert.
+ *
+ * Every value has to belong to a field and every required field needs a value. Returns the
+ * normalized values in the order of the alert fields.
+ */
+pub fn normalize_field_values<A: Api>(
+    api: &A,
+    alert_fields: &[AlertField],
+    field_values: &[SubscriptionFieldValue],
+) -> Result<Vec<SubscriptionFieldValue>, ContractError> {
+    // Throw error if a value is given twice or for a field the alert doesn't have
+    for (i, field_value) in field_values.iter().enumerate() {
+        if field_values[..i]
+            .iter()
+            .any(|f| f.field_key == field_value.field_key)
+        {
+            return Err(ContractError::InvalidField {
+                field_key: field_value.field_key.clone(),
+                reason: "duplicate field key".to_string(),
+            });
+        }
+        if !alert_fields
+            .iter()
+            .any(|alert_field| alert_field.field_key == field_value.field_key)
+        {
+            return Err(ContractError::UnknownField {
+                field_key: field_value.field_key.clone(),
+            });
+        }
+    }
+
+    let mut normalized_field_values: Vec<SubscriptionFieldValue> = Vec::new();
+    for alert_field in alert_fields {
+        let field_value: &SubscriptionFieldValue = match field_values
+            .iter()
+            .find(|field_value| field_value.field_key == alert_field.field_key)
+        {
+            Some(field_value) => field_value,
+            None if alert_field.optional => continue,
+            // Throw error if a required value for a field is missing
+            None => {
+                return Err(ContractError::MissingField {
+                    field_key: alert_field.field_key.clone(),
+                })
+            }
+        };
+        // Throw error if a field value does not match its type or regex expression
+        let value: String = normalize_field_value(api, alert_field, &field_value.value)?;
+        normalized_field_values.push(SubscriptionFieldValue {
+            field_key: alert_field.field_key.clone(),
+            value,
+        });
+    }
+    Ok(normalized_field_values)
+}
+
+/**
+ * Checks a subscription value against the type and validation regex of its field.
+ *
+ * Returns the value in a canonical form (e.g. `0010.50` becomes `10.5` for decimals),
+ * so the notifier never has to deal with different spellings of the same value.
+ * The validation regex is matched against this normalized value.
+ */
+pub fn normalize_field_value<A: Api>(
+    api: &A,
+    field: &AlertField,
+    value: &str,
+) -> Result<String, ContractError> {
+    let normalized: Option<String> = match &field.field_type {
+        AlertFieldType::Text => Some(value.to_string()),
+        AlertFieldType::Address => api
+            .canonical_address(&HumanAddr::from(value))
+            .and_then(|canonical_addr| api.human_address(&canonical_addr))
+            .ok()
+            .map(|human_addr| human_addr.to_string()),
+        AlertFieldType::Uint128 => value.parse::<u128>().ok().map(|v| v.to_string()),
+        AlertFieldType::Decimal => normalize_decimal(value),
+        AlertFieldType::Percentage => normalize_decimal(value).filter(|v| is_percentage(v)),
+        AlertFieldType::Denom => {
+            if is_denom(value) {
+                Some(value.to_string())
+            } else {
+                None
+            }
+        }
+        AlertFieldType::Enum { options } => options.iter().find(|o| *o == value).cloned(),
+        AlertFieldType::Bool => {
+            if value.eq_ignore_ascii_case("true") {
+                Some("true".to_string())
+            } else if value.eq_ignore_ascii_case("false") {
+                Some("false".to_string())
+            } else {
+                None
+            }
+        }
+    };
+
+    let normalized: String = normalized.ok_or_else(|| ContractError::InvalidField {
+        field_key: field.field_key.clone(),
+        reason: format!("expected {}", describe_field_type(&field.field_type)),
+    })?;
+    if !Regex::new(&field.validation_regex)?.is_match(&normalized) {
+        return Err(ContractError::InvalidField {
+            field_key: field.field_key.clone(),
+            reason: format!("does not match {}", field.validation_regex),
+        });
+    }
+
+    Ok(normalized)
+}
+
+fn describe_field_type(field_type: &AlertFieldType) -> String {
+    match field_type {
+        AlertFieldType::Text => "text".to_string(),
+        AlertFieldType::Address => "an address".to_string(),
+        AlertFieldType::Uint128 => "an unsigned integer".to_string(),
+        AlertFieldType::Decimal => format!("a decimal with at most {} places", DECIMAL_PLACES),
+        AlertFieldType::Percentage => "a percentage between 0 and 100".to_string(),
+        AlertFieldType::Denom => "a native denom".to_string(),
+        AlertFieldType::Enum { options } => format!("one of {}", options.join(", ")),
+        AlertFieldType::Bool => "true or false".to_string(),
+    }
+}
+
+// Parses a non-negative decimal and strips redundant zeros, returns None if it is invalid
+fn normalize_decimal(value: &str) -> Option<String> {
+    let mut parts = value.splitn(2, '.');
+    let whole: &str = parts.next().unwrap_or("");
+    let fraction: &str = parts.next().unwrap_or("");
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > DECIMAL_PLACES
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let whole: &str = whole.trim_start_matches('0');
+    let fraction: &str = fraction.trim_end_matches('0');
+    let whole: &str = if whole.is_empty() { "0" } else { whole };
+    if fraction.is_empty() {
+        Some(whole.to_string())
+    } else {
+        Some(format!("{}.{}", whole, fraction))
+    }
+}
+
+// Native denoms such as `uusd` or `ibc/27394FB0...`, same rules as the Cosmos SDK
+fn is_denom(value: &str) -> bool {
+    let mut chars = value.chars();
+    value.len() >= 3
+        && value.len() <= 128
+        && matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c))
+}
+
+// Expects a decimal that was already normalized
+fn is_percentage(value: &str) -> bool {
+    let whole: &str = value.split('.').next().unwrap_or("");
+    match whole.parse::<u8>() {
+        Ok(100) => !value.contains('.'),
+        Ok(whole) => whole < 100,
+        Err(_) => false,
+    }
+}
+
+/**
+ * A small regular expression engine for validating subscription field values.
+ *
+ * The `regex` crate adds several hundred kilobytes to the contract binary, so we compile
+ * a restricted subset of its syntax into a Pike VM instead. Matching runs in
+ * O(pattern * input) time and never backtracks. Pattern length, program size and compile work
+ * are all capped, which keeps gas usage predictable.
+ *
+ * Supported syntax:
+ * - Literals, `.` and escapes (`\d \D \w \W \s \S \n \r \t` and escaped punctuation)
+ * - Character classes such as `[a-z0-9_]` and `[^,]`
+ * - Anchors `^` and `$`
+ * - Groups `(...)` / `(?:...)` and alternation `|`
+ * - Quantifiers `*`, `+`, `?`, `{m}`, `{m,}` and `{m,n}` (a trailing `?` is accepted and ignored)
+ *
+ * Like `Regex::is_match`, a pattern matches if it matches anywhere in the input,
+ * so patterns should be anchored with `^...$` to validate a whole value.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    // Compiles a pattern, returning an error if it is malformed or uses unsupported syntax
+    pub fn new(pattern: &str) -> Result<Regex, ContractError> {
+        if pattern.len() > MAX_PATTERN_LEN {
+            return Err(invalid_regex(
+                pattern,
+                &format!("pattern is longer than {} bytes", MAX_PATTERN_LEN),
+            ));
+        }
+
+        let node = Parser::new(pattern)
+            .parse()
+            .map_err(|reason| invalid_regex(pattern, &reason))?;
+        let mut compiler = Compiler {
+            program: Vec::new(),
+            steps: 0,
+        };
+        compiler
+            .compile(&node)
+            .map_err(|reason| invalid_regex(pattern, &reason))?;
+        compiler.program.push(Inst::Match);
+
+        Ok(Regex {
+            program: compiler.program,
+        })
+    }
+
+    // Returns true if the pattern matches anywhere in `text`
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let len = chars.len();
+        let mut current = ThreadList::new(self.program.len());
+        let mut next = ThreadList::new(self.program.len());
+
+        for pos in 0..=len {
+            // Start a new thread at every position so the pattern can match anywhere
+            self.add_thread(&mut current, 0, pos, len);
+            let c = chars.get(pos);
+            for &pc in current.pcs.iter() {
+                match &self.program[pc] {
+                    Inst::Match => return true,
+                    Inst::Class(class) if matches!(c, Some(c) if class.matches(*c)) => {
+                        self.add_thread(&mut next, pc + 1, pos + 1, len);
+                    }
+                    _ => {}
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+
+        false
+    }
+
+    // Follows all empty transitions from `pc` and adds the reachable states to `list`
+    fn add_thread(&self, list: &mut ThreadList, pc: usize, pos: usize, len: usize) {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if !list.insert(pc) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jmp(target) => stack.push(*target),
+                Inst::Split(first, second) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Inst::Start => {
+                    if pos == 0 {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::End => {
+                    if pos == len {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::Class(_) | Inst::Match => {}
+            }
+        }
+    }
+}
+
+fn invalid_regex(pattern: &str, reason: &str) -> ContractError {
+    ContractError::InvalidRegex {
+        pattern: pattern.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Inst {
+    Class(CharClass),
+    Start,
+    End,
+    Jmp(usize),
+    Split(usize, usize),
+    Match,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn single(c: char) -> CharClass {
+        CharClass {
+            negated: false,
+            ranges: vec![(c, c)],
+        }
+    }
+
+    // `.` matches everything except a newline
+    fn any() -> CharClass {
+        CharClass {
+            negated: true,
+            ranges: vec![('\n', '\n')],
+        }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        let in_ranges = self
+            .ranges
+            .iter()
+            .any(|(start, end)| *start <= c && c <= *end);
+        in_ranges != self.negated
+    }
+}
+
+fn digit_ranges() -> Vec<(char, char)> {
+    vec![('0', '9')]
+}
+
+fn word_ranges() -> Vec<(char, char)> {
+    vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]
+}
+
+fn space_ranges() -> Vec<(char, char)> {
+    vec![('\t', '\r'), (' ', ' ')]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Empty,
+    Class(CharClass),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(pattern: &str) -> Parser {
+        Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn parse(&mut self) -> Result<Node, String> {
+        let node = self.parse_alternation()?;
+        match self.peek() {
+            None => Ok(node),
+            Some(')') => Err("unopened group".to_string()),
+            Some(c) => Err(format!("unexpected character '{}'", c)),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        if branches.len() == 1 {
+            Ok(branches.remove(0))
+        } else {
+            Ok(Node::Alternate(branches))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            nodes.push(self.parse_repeat()?);
+        }
+        match nodes.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(nodes.remove(0)),
+            _ => Ok(Node::Concat(nodes)),
+        }
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_atom()?;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                Some('{') => {
+                    self.pos += 1;
+                    self.parse_counted()?
+                }
+                _ => return Ok(node),
+            };
+            if let Node::Start | Node::End | Node::Empty = node {
+                return Err("repetition operator missing expression".to_string());
+            }
+            // Laziness does not change whether a pattern matches, so `*?` is the same as `*`
+            self.eat('?');
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+    }
+
+    // Parses the `m}`, `m,}` or `m,n}` following a `{`
+    fn parse_counted(&mut self) -> Result<(u32, Option<u32>), String> {
+        let min = self.parse_number()?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.parse_number()?)
+            }
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') {
+            return Err("unclosed counted repetition".to_string());
+        }
+        if let Some(max) = max {
+            if max < min {
+                return Err(format!("invalid repetition range {{{},{}}}", min, max));
+            }
+        }
+        if min > MAX_REPEAT || max.unwrap_or(0) > MAX_REPEAT {
+            return Err(format!("repetition count exceeds {}", MAX_REPEAT));
+        }
+        Ok((min, max))
+    }
+
+    fn parse_number(&mut self) -> Result<u32, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err("expected number in counted repetition".to_string());
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse::<u32>()
+            .map_err(|_| format!("repetition count {} is too large", digits))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            None => Err("unexpected end of pattern".to_string()),
+            Some('(') => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err("only non-capturing groups (?:...) are supported".to_string());
+                }
+                let node = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err("unclosed group".to_string());
+                }
+                Ok(node)
+            }
+            Some('[') => self.parse_class(),
+            Some('.') => Ok(Node::Class(CharClass::any())),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => self.parse_escape(),
+            Some(c) if c == '*' || c == '+' || c == '?' || c == '{' => {
+                Err("repetition operator missing expression".to_string())
+            }
+            Some(c) => Ok(Node::Class(CharClass::single(c))),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let c = self
+            .next()
+            .ok_or_else(|| "incomplete escape sequence".to_string())?;
+        let (negated, ranges) = match c {
+            'd' => (false, digit_ranges()),
+            'D' => (true, digit_ranges()),
+            'w' => (false, word_ranges()),
+            'W' => (true, word_ranges()),
+            's' => (false, space_ranges()),
+            'S' => (true, space_ranges()),
+            _ => {
+                let literal = escaped_literal(c)?;
+                (false, vec![(literal, literal)])
+            }
+        };
+        Ok(Node::Class(CharClass { negated, ranges }))
+    }
+
+    // Parses a bracketed class after the opening `[`
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self
+                .next()
+                .ok_or_else(|| "unclosed character class".to_string())?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let start = if c == '\\' {
+                match self.next() {
+                    Some('d') => {
+                        ranges.extend(digit_ranges());
+                        continue;
+                    }
+                    Some('w') => {
+                        ranges.extend(word_ranges());
+                        continue;
+                    }
+                    Some('s') => {
+                        ranges.extend(space_ranges());
+                        continue;
+                    }
+                    Some(c) if c == 'D' || c == 'W' || c == 'S' => {
+                        return Err(format!(
+                            "negated escape \\{} is not supported inside a character class",
+                            c
+                        ));
+                    }
+                    Some(c) => escaped_literal(c)?,
+                    None => return Err("incomplete escape sequence".to_string()),
+                }
+            } else {
+                c
+            };
+
+            // A `-` is a range operator unless it is the last character in the class
+            let is_range = self.peek() == Some('-')
+                && matches!(self.chars.get(self.pos + 1), Some(c) if *c != ']');
+            if is_range {
+                self.pos += 1;
+                let end = match self.next() {
+                    Some('\\') => {
+                        let c = self
+                            .next()
+                            .ok_or_else(|| "incomplete escape sequence".to_string())?;
+                        escaped_literal(c)?
+                    }
+                    Some(c) => c,
+                    None => return Err("unclosed character class".to_string()),
+                };
+                if end < start {
+                    return Err(format!("invalid class range {}-{}", start, end));
+                }
+                ranges.push((start, end));
+            } else {
+                ranges.push((start, start));
+            }
+        }
+        Ok(Node::Class(CharClass { negated, ranges }))
+    }
+}
+
+// Resolves an escape that stands for a single character
+fn escaped_literal(c: char) -> Result<char, String> {
+    match c {
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        't' => Ok('\t'),
+        c if c.is_ascii_punctuation() => Ok(c),
+        c => Err(format!("unsupported escape sequence \\{}", c)),
+    }
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+    // Number of nodes compiled so far, repeated nodes count once per copy
+    steps: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM_LEN {
+            return Err("pattern is too complex".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > MAX_COMPILE_STEPS {
+            return Err("pattern is too complex".to_string());
+        }
+        match node {
+            Node::Empty => {}
+            Node::Class(class) => {
+                self.emit(Inst::Class(class.clone()))?;
+            }
+            Node::Start => {
+                self.emit(Inst::Start)?;
+            }
+            Node::End => {
+                self.emit(Inst::End)?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.compile(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                // split L1, next; L1: branch; jmp end; next: split L2, ... ; last branch; end:
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(branch)?;
+                        jumps.push(self.emit(Inst::Jmp(0))?);
+                        let next = self.program.len();
+                        self.program[split] = Inst::Split(split + 1, next);
+                    } else {
+                        self.compile(branch)?;
+                    }
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jmp(end);
+                }
+            }
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    // loop: split body, end; body; jmp loop; end:
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.emit(Inst::Jmp(split))?;
+                        let end = self.program.len();
+                        self.program[split] = Inst::Split(split + 1, end);
+                    }
+                    // Each optional copy may skip straight to the end
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// The set of program counters active at one input position
+struct ThreadList {
+    pcs: Vec<usize>,
+    present: Vec<bool>,
+}
+
+impl ThreadList {
+    fn new(size: usize) -> ThreadList {
+        ThreadList {
+            pcs: Vec::with_capacity(size),
+            present: vec![false; size],
+        }
+    }
+
+    // Returns false if `pc` was already in the list
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.present[pc] {
+            return false;
+        }
+        self.present[pc] = true;
+        self.pcs.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for pc in self.pcs.drain(..) {
+            self.present[pc] = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockApi;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn matches_literals_and_anchors() {
+        assert!(is_match("abc", "xxabcxx"));
+        assert!(!is_match("^abc$", "xxabcxx"));
+        assert!(is_match("^abc$", "abc"));
+        assert!(is_match("", "anything"));
+        assert!(is_match("^$", ""));
+        assert!(!is_match("^$", "a"));
+    }
+
+    #[test]
+    fn matches_classes_and_escapes() {
+        assert!(is_match(r"^\d+$", "12345"));
+        assert!(!is_match(r"^\d+$", "12a45"));
+        assert!(is_match(r"^[a-z0-9_]+$", "terra_1"));
+        assert!(!is_match(r"^[a-z0-9_]+$", "Terra"));
+        assert!(is_match(r"^[^,]+$", "no commas"));
+        assert!(!is_match(r"^[^,]+$", "a,b"));
+        assert!(is_match(r"^\d+\.\d+$", "1.50"));
+        assert!(!is_match(r"^\d+\.\d+$", "1x50"));
+        assert!(is_match(r"^[-+]?\d+$", "-5"));
+        assert!(is_match(r"^a.c$", "abc"));
+        assert!(!is_match(r"^a.c$", "a\nc"));
+    }
+
+    #[test]
+    fn matches_groups_and_quantifiers() {
+        assert!(is_match("^(ab)+$", "ababab"));
+        assert!(!is_match("^(ab)+$", "ababa"));
+        assert!(is_match("^(?:up|down)$", "down"));
+        assert!(!is_match("^(?:up|down)$", "sideways"));
+        assert!(is_match(r"^\d{3}$", "123"));
+        assert!(!is_match(r"^\d{3}$", "1234"));
+        assert!(is_match(r"^\d{2,4}$", "1234"));
+        assert!(!is_match(r"^\d{2,4}$", "1"));
+        assert!(is_match(r"^\d{2,}$", "123456"));
+        assert!(is_match(
+            "^terra1[a-z0-9]{38}$",
+            "terra1dcegyrekltswvyy0xy69ydgxn9x8x32zdtapd8"
+        ));
+        assert!(is_match("^(a*)*$", "aaaa"));
+        assert!(is_match("^a*?b$", "aab"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        for pattern in &[
+            "(abc", "abc)", "[abc", "*a", "a{2", "a{3,1}", "a{1000}", r"\p{L}", "(?i)abc", r"\",
+            "[z-a]", "^*",
+        ] {
+            assert!(
+                Regex::new(pattern).is_err(),
+                "{} should be invalid",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_patterns() {
+        let pattern = "a".repeat(MAX_PATTERN_LEN + 1);
+        assert!(Regex::new(&pattern).is_err());
+        assert!(Regex::new("(a{100}){100}").is_err());
+    }
+
+    fn field(field_type: AlertFieldType, validation_regex: &str) -> AlertField {
+        AlertField {
+            field_key: "threshold".to_string(),
+            name: "Threshold".to_string(),
+            description: "".to_string(),
+            field_type,
+            validation_regex: validation_regex.to_string(),
+            optional: false,
+        }
+    }
+
+    fn normalize(field_type: AlertFieldType, value: &str) -> Result<String, ContractError> {
+        normalize_field_value(&MockApi::new(20), &field(field_type, ""), value)
+    }
+
+    #[test]
+    fn normalizes_typed_values() {
+        assert_eq!(normalize(AlertFieldType::Text, " any ").unwrap(), " any ");
+        assert_eq!(
+            normalize(AlertFieldType::Address, "subscriber").unwrap(),
+            "subscriber"
+        );
+        assert_eq!(
+            normalize(AlertFieldType::Uint128, "001000").unwrap(),
+            "1000"
+        );
+        assert_eq!(
+            normalize(AlertFieldType::Decimal, "0010.500").unwrap(),
+            "10.5"
+        );
+        assert_eq!(normalize(AlertFieldType::Decimal, ".25").unwrap(), "0.25");
+        assert_eq!(normalize(AlertFieldType::Decimal, "3.").unwrap(), "3");
+        assert_eq!(
+            normalize(AlertFieldType::Percentage, "100.0").unwrap(),
+            "100"
+        );
+        assert_eq!(
+            normalize(AlertFieldType::Percentage, "99.99").unwrap(),
+            "99.99"
+        );
+        assert_eq!(normalize(AlertFieldType::Denom, "uusd").unwrap(), "uusd");
+        assert_eq!(normalize(AlertFieldType::Bool, "TRUE").unwrap(), "true");
+        let options = vec!["above".to_string(), "below".to_string()];
+        assert_eq!(
+            normalize(AlertFieldType::Enum { options }, "below").unwrap(),
+            "below"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_typed_values() {
+        assert!(normalize(AlertFieldType::Address, "ab").is_err());
+        assert!(normalize(AlertFieldType::Uint128, "-1").is_err());
+        assert!(normalize(AlertFieldType::Uint128, "1.5").is_err());
+        assert!(normalize(AlertFieldType::Decimal, ".").is_err());
+        assert!(normalize(AlertFieldType::Decimal, "1.2.3").is_err());
+        assert!(normalize(AlertFieldType::Decimal, "0.1234567890123456789").is_err());
+        assert!(normalize(AlertFieldType::Percentage, "100.01").is_err());
+        assert!(normalize(AlertFieldType::Percentage, "250").is_err());
+        assert!(normalize(AlertFieldType::Denom, "u").is_err());
+        assert!(normalize(AlertFieldType::Bool, "yes").is_err());
+        let options = vec!["above".to_string(), "below".to_string()];
+        assert!(normalize(AlertFieldType::Enum { options }, "Above").is_err());
+    }
+
+    #[test]
+    fn checks_regex_against_normalized_value() {
+        let api = MockApi::new(20);
+        let field = field(AlertFieldType::Uint128, "^[1-9][0-9]*$");
+        assert_eq!(normalize_field_value(&api, &field, "007").unwrap(), "7");
+        assert!(normalize_field_value(&api, &field, "0").is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_field_keys() {
+        let fields = vec![
+            field(AlertFieldType::Text, ""),
+            field(AlertFieldType::Uint128, ""),
+        ];
+        assert!(validate_alert_fields(&fields[..1]).is_ok());
+        assert!(validate_alert_fields(&fields).is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_field_values() {
+        let api = MockApi::new(20);
+        let fields = vec![field(AlertFieldType::Uint128, "")];
+        let value = |value: &str| SubscriptionFieldValue {
+            field_key: "threshold".to_string(),
+            value: value.to_string(),
+        };
+        assert!(normalize_field_values(&api, &fields, &[value("1")]).is_ok());
+        match normalize_field_values(&api, &fields, &[value("1"), value("2")]) {
+            Err(ContractError::InvalidField { field_key, reason }) => {
+                assert_eq!(field_key, "threshold");
+                assert_eq!(reason, "duplicate field key");
+            }
+            res => panic!("Must return InvalidField, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_field_definitions() {
+        let options = vec![];
+        assert!(validate_alert_field(&field(AlertFieldType::Enum { options }, "")).is_err());
+        assert!(validate_alert_field(&field(AlertFieldType::Text, "(")).is_err());
+        assert!(validate_alert_field(&field(AlertFieldType::Text, "^a+$")).is_ok());
+    }
+
+    #[test]
+    fn rejects_nested_repetitions_of_empty_groups() {
+        // Compiles to no instructions at all, so only the step limit stops it
+        match Regex::new("(()()){100}{100}{100}{100}") {
+            Err(ContractError::InvalidRegex { reason, .. }) => {
+                assert_eq!(reason, "pattern is too complex")
+            }
+            res => panic!("Must return InvalidRegex, got {:?}", res),
+        }
+        assert!(Regex::new("^(()()){100}a$").unwrap().is_match("a"));
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        let tags = |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| t.to_string()).collect() };
+        assert_eq!(
+            normalize_tags(&tags(&["Liquidation", "ltv", "liquidation"])).unwrap(),
+            tags(&["liquidation", "ltv"])
+        );
+        assert!(normalize_tags(&tags(&["price feed"])).is_err());
+        assert!(normalize_tags(&tags(&[""])).is_err());
+        assert!(normalize_tags(&tags(&["a"; MAX_TAGS + 1])).is_err());
+    }
+
+    #[test]
+    fn rejects_zero_subscription_limits() {
+        assert!(validate_subscription_limits(&SubscriptionLimits::default()).is_ok());
+        let zeroed: [fn(&mut SubscriptionLimits); 3] = [
+            |limits| limits.max_subscriptions_per_address = 0,
+            |limits| limits.max_field_values = 0,
+            |limits| limits.max_value_len = 0,
+        ];
+        for zero in zeroed.iter() {
+            let mut limits = SubscriptionLimits::default();
+            zero(&mut limits);
+            match validate_subscription_limits(&limits) {
+                Err(ContractError::InvalidLimits { .. }) => {}
+                res => panic!("Must return InvalidLimits, got {:?}", res),
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use std::env::temp_dir;
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all};
+use std::path::{Path, PathBuf};
+
+#[allow(dead_code)]
+#[path = "../examples/schema.rs"]
+mod schema;
+
+// File name -> content of the JSON schemas in a directory
+fn read_schemas(dir: &Path) -> BTreeMap<String, String> {
+    read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, read_to_string(&path).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn checked_in_schemas_are_up_to_date() {
+    let out_dir: PathBuf = temp_dir().join(format!("asteroid-alert-schema-{}", std::process::id()));
+    create_dir_all(&out_dir).unwrap();
+    schema::export_schemas(&out_dir);
+    let generated = read_schemas(&out_dir);
+    remove_dir_all(&out_dir).unwrap();
+
+    let checked_in = read_schemas(&Path::new(env!("CARGO_MANIFEST_DIR")).join("schema"));
+    assert_eq!(
+        generated.keys().collect::<Vec<_>>(),
+        checked_in.keys().collect::<Vec<_>>(),
+        "schema/ has missing or stale files, run `cargo schema`"
+    );
+    for (name, content) in &generated {
+        assert_eq!(
+            content, &checked_in[name],
+            "schema/{} is out of date, run `cargo schema`",
+            name
+        );
+    }
+}